@@ -10,7 +10,8 @@ fn main() {
             println!("cargo:rustc-link-lib=citro2d");
             println!("cargo:rustc-link-lib=citro3d");
         } else if let Ok(arch) = std::env::var("CARGO_CFG_TARGET_ARCH") {
-            if arch != "wasm32" {
+            // the bindings are only used by the SDL platform
+            if arch != "wasm32" && std::env::var_os("CARGO_FEATURE_SDL").is_some() {
                 let dest = std::env::var("OUT_DIR").unwrap();
                 let mut file = File::create(Path::new(&dest).join("gl_bindings.rs")).unwrap();
 
@@ -15,6 +15,41 @@ def write_co(file, co):
         v = int(scalar * 256)
         file.write(struct.pack('<h', v))
 
+DEFAULT_WIDTH = 4.0
+
+SURFACE_KINDS = {'boost': 0, 'rough': 1, 'ice': 2}
+
+def parse_surfaces(text):
+    # zones are separated by semicolons, each written as "kind start end left right"
+    surfaces = []
+    for zone in text.split(';'):
+        parts = zone.split()
+        if not parts:
+            continue
+        kind, start, end, left, right = parts
+        surfaces.append((SURFACE_KINDS[kind], float(start), float(end), float(left), float(right)))
+    return surfaces
+
+def parse_gaps(text):
+    # gaps are separated by semicolons, each written as "start end"
+    gaps = []
+    for gap in text.split(';'):
+        parts = gap.split()
+        if not parts:
+            continue
+        start, end = parts
+        gaps.append((float(start), float(end)))
+    return gaps
+
+def pack_string(s):
+    data = s.encode('utf-8')
+    return struct.pack('<H', len(data)) + data
+
+def write_chunk(file, tag, payload):
+    file.write(tag)
+    file.write(struct.pack('<I', len(payload)))
+    file.write(payload)
+
 class BezierExport(bpy.types.Operator, bpy_extras.io_utils.ExportHelper):
     """Export the selected Bezier curve."""
     bl_idname = 'condux.bezier_export'
@@ -22,6 +57,10 @@ class BezierExport(bpy.types.Operator, bpy_extras.io_utils.ExportHelper):
 
     filename_ext = '.bin'
 
+    course_name: bpy.props.StringProperty(name='Name', description='Name of the course')
+    course_author: bpy.props.StringProperty(name='Author', description='Author of the course')
+    course_laps: bpy.props.IntProperty(name='Laps', description='Number of laps, or 0 for the default', min=0, max=65535)
+
     def execute(self, context):
         try:
             # get the active curve
@@ -29,12 +68,59 @@ class BezierExport(bpy.types.Operator, bpy_extras.io_utils.ExportHelper):
             # get the points
             points = curve.splines[0].bezier_points
             with open(self.filepath, 'wb') as file:
-                # write number of points
-                file.write(bytes([len(points)]))
-                # iterate over the points in the curve - export them as 8.8
+                # write magic and version
+                file.write(b'CDXC')
+                file.write(bytes([2]))
+                # write metadata
+                meta = pack_string(self.course_name) + pack_string(self.course_author) + struct.pack('<H', self.course_laps)
+                write_chunk(file, b'META', meta)
+                # write number of points, then each point as floats
+                data = struct.pack('<H', len(points))
+                for point in points:
+                    co = point.co
+                    data += struct.pack('<ffff', co.x, co.z, co.y, point.tilt)
+                write_chunk(file, b'PNTS', data)
+                # curves that aren't cyclic are point-to-point courses
+                if not curve.splines[0].use_cyclic_u:
+                    write_chunk(file, b'OPEN', b'')
+                # write widths, scaling the default width by each point's radius
+                data = struct.pack('<H', len(points))
                 for point in points:
-                    write_co(file, point.co)
-                    file.write(bytes([int(256 * ((point.tilt % (2 * math.pi)) / (2 * math.pi)))]))
+                    data += struct.pack('<f', point.radius * DEFAULT_WIDTH)
+                write_chunk(file, b'WDTH', data)
+                # write surface zones from the object's custom property
+                surfaces = parse_surfaces(context.active_object.get('condux_surfaces', ''))
+                if surfaces:
+                    data = struct.pack('<H', len(surfaces))
+                    for surface in surfaces:
+                        data += struct.pack('<Bffff', *surface)
+                    write_chunk(file, b'SURF', data)
+                # write gaps from the object's custom property
+                gaps = parse_gaps(context.active_object.get('condux_gaps', ''))
+                if gaps:
+                    data = struct.pack('<H', len(gaps))
+                    for gap in gaps:
+                        data += struct.pack('<ff', *gap)
+                    write_chunk(file, b'GAPS', data)
+                # write tubes from the object's custom property
+                tubes = parse_gaps(context.active_object.get('condux_tubes', ''))
+                if tubes:
+                    data = struct.pack('<H', len(tubes))
+                    for tube in tubes:
+                        data += struct.pack('<ff', *tube)
+                    write_chunk(file, b'TUBE', data)
+                # write branches, using the rest of the curve's splines as their points
+                branches = parse_gaps(context.active_object.get('condux_branches', ''))
+                branches = list(zip(branches, curve.splines[1:]))
+                if branches:
+                    data = struct.pack('<H', len(branches))
+                    for (fork, merge), spline in branches:
+                        branch_points = spline.bezier_points
+                        data += struct.pack('<ffH', fork, merge, len(branch_points))
+                        for point in branch_points:
+                            co = point.co
+                            data += struct.pack('<fffff', co.x, co.z, co.y, point.tilt, point.radius * DEFAULT_WIDTH)
+                    write_chunk(file, b'BRCH', data)
         except BaseException as e:
             self.report({'ERROR'}, repr(e))
             return {'CANCELLED'}
@@ -14,43 +14,371 @@
 //! You should have received a copy of the GNU General Public License
 //! along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::{borrow::Cow, error::Error, fmt};
+
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
 use include_dir::{include_dir, Dir};
 
 use crate::linalg::Vector;
 
+/// The default assets, built into the game.
 static ASSETS: Dir = include_dir!("$CARGO_MANIFEST_DIR/assets");
 
+/// The directory custom assets are loaded from, unless overridden by the
+/// CONDUX_CONTENT environment variable.
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+const CONTENT_DIR: &str = "content";
+
+/// The directory custom assets are loaded from.
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+fn content_dir() -> PathBuf {
+    std::env::var_os("CONDUX_CONTENT")
+        .unwrap_or_else(|| CONTENT_DIR.into())
+        .into()
+}
+
+/// Why an asset couldn't be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssetErrorKind {
+    /// There is no asset with the name.
+    NotFound,
+    /// The asset ended before everything was read.
+    Truncated,
+    /// An index refers to something that doesn't exist.
+    IndexOutOfRange,
+    /// A course has too few points to form a loop.
+    TooFewPoints,
+    /// Points are placed such that the shape can't be built, such as two
+    /// points in the same place.
+    DegenerateGeometry,
+    /// The asset should be text but isn't valid UTF-8.
+    InvalidText,
+    /// The asset exists but couldn't be read.
+    Unreadable(String),
+    /// The asset doesn't list something with the given name.
+    MissingEntry(String),
+    /// The asset is a newer version than can be read.
+    UnsupportedVersion(u8),
+    /// A chunk's contents don't match its length.
+    MalformedChunk(String),
+    /// A chunk that must be present is missing.
+    MissingChunk(String),
+}
+
+impl fmt::Display for AssetErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "not found"),
+            Self::Truncated => write!(f, "unexpected end of file"),
+            Self::IndexOutOfRange => write!(f, "index out of range"),
+            Self::TooFewPoints => write!(f, "too few points"),
+            Self::DegenerateGeometry => write!(f, "degenerate geometry"),
+            Self::InvalidText => write!(f, "invalid text"),
+            Self::Unreadable(reason) => write!(f, "unreadable: {reason}"),
+            Self::MissingEntry(name) => write!(f, "no entry named {name}"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported version {version}"),
+            Self::MalformedChunk(tag) => write!(f, "malformed {tag} chunk"),
+            Self::MissingChunk(tag) => write!(f, "missing {tag} chunk"),
+        }
+    }
+}
+
+/// An error loading an asset, with where in the asset it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetError {
+    /// The name of the asset.
+    pub file: String,
+    /// The byte offset the error was found at.
+    pub offset: usize,
+    /// Why the asset couldn't be loaded.
+    pub kind: AssetErrorKind,
+}
+
+impl AssetError {
+    #[must_use]
+    pub fn new(file: &str, offset: usize, kind: AssetErrorKind) -> Self {
+        Self {
+            file: String::from(file),
+            offset,
+            kind,
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at byte {}: {}", self.file, self.offset, self.kind)
+    }
+}
+
+impl Error for AssetError {}
+
 pub struct Asset {
-    contents: &'static [u8],
+    /// The name of the asset, used in errors.
+    name: String,
+    contents: Cow<'static, [u8]>,
     index: usize,
 }
 
 impl Asset {
+    /// Load an asset. Custom assets take priority over the default ones.
+    pub fn load(name: &str) -> Result<Self, AssetError> {
+        #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+        if let Some(asset) = Self::load_custom(name)? {
+            return Ok(asset);
+        }
+        Self::load_default(name)
+    }
+
+    /// Load every copy of an asset, the default one first, followed by the
+    /// custom one if there is one. Used for assets that add to each other
+    /// rather than replacing each other.
+    pub fn load_all(name: &str) -> Result<Vec<Self>, AssetError> {
+        let mut assets = vec![];
+        match Self::load_default(name) {
+            Ok(asset) => assets.push(asset),
+            Err(e) if e.kind == AssetErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+        assets.extend(Self::load_custom(name)?);
+        if assets.is_empty() {
+            return Err(AssetError::new(name, 0, AssetErrorKind::NotFound));
+        }
+        Ok(assets)
+    }
+
+    /// Load an asset built into the game.
+    fn load_default(name: &str) -> Result<Self, AssetError> {
+        let contents = ASSETS
+            .get_file(name)
+            .ok_or_else(|| AssetError::new(name, 0, AssetErrorKind::NotFound))?
+            .contents();
+        Ok(Self::from_contents(name, Cow::Borrowed(contents)))
+    }
+
+    /// Load an asset from the content directory, or None if it isn't there.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    fn load_custom(name: &str) -> Result<Option<Self>, AssetError> {
+        match std::fs::read(Self::custom_path(name)) {
+            Ok(bytes) => Ok(Some(Self::from_bytes(name, bytes))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(AssetError::new(
+                name,
+                0,
+                AssetErrorKind::Unreadable(e.to_string()),
+            )),
+        }
+    }
+
+    /// The path of the custom copy of an asset in the content directory,
+    /// which may not exist yet.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    #[must_use]
+    pub fn custom_path(name: &str) -> PathBuf {
+        content_dir().join(name)
+    }
+
+    /// Read an asset from bytes that were loaded elsewhere, such as from a
+    /// user's file.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
     #[must_use]
-    pub fn load(name: &str) -> Option<Self> {
-        let contents = ASSETS.get_file(name)?.contents();
-        Some(Self { contents, index: 0 })
+    pub fn from_bytes(name: &str, bytes: Vec<u8>) -> Self {
+        Self::from_contents(name, Cow::Owned(bytes))
+    }
+
+    fn from_contents(name: &str, contents: Cow<'static, [u8]>) -> Self {
+        Self {
+            name: String::from(name),
+            contents,
+            index: 0,
+        }
+    }
+
+    /// The names of every asset, default and custom, in sorted order.
+    #[must_use]
+    pub fn names() -> Vec<String> {
+        let mut names: Vec<String> = ASSETS
+            .files()
+            .filter_map(|file| file.path().to_str())
+            .map(String::from)
+            .collect();
+        #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+        if let Ok(dir) = std::fs::read_dir(content_dir()) {
+            names.extend(dir.filter_map(|entry| entry.ok()?.file_name().into_string().ok()));
+        }
+        names.sort();
+        names.dedup();
+        names
     }
 
-    pub fn read_byte(&mut self) -> Option<u8> {
+    /// The position of the next byte to be read.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.index
+    }
+
+    /// The number of bytes left to read.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.contents.len().saturating_sub(self.index)
+    }
+
+    /// Move to a position in the asset.
+    pub fn seek(&mut self, offset: usize) {
+        self.index = offset;
+    }
+
+    /// Create an error at the current position in the asset.
+    #[must_use]
+    pub fn error(&self, kind: AssetErrorKind) -> AssetError {
+        self.error_at(self.index, kind)
+    }
+
+    /// Create an error at the given position in the asset.
+    #[must_use]
+    pub fn error_at(&self, offset: usize, kind: AssetErrorKind) -> AssetError {
+        AssetError::new(&self.name, offset, kind)
+    }
+
+    /// Read the rest of the asset as text.
+    pub fn into_string(self) -> Result<String, AssetError> {
+        let rest = self.contents.get(self.index..).unwrap_or_default();
+        String::from_utf8(rest.to_vec()).map_err(|e| {
+            let offset = self.index + e.utf8_error().valid_up_to();
+            AssetError::new(&self.name, offset, AssetErrorKind::InvalidText)
+        })
+    }
+
+    pub fn read_byte(&mut self) -> Result<u8, AssetError> {
         if self.index >= self.contents.len() {
-            return None;
+            return Err(self.error(AssetErrorKind::Truncated));
         }
         let b = self.contents[self.index];
         self.index += 1;
-        Some(b)
+        Ok(b)
     }
 
-    pub fn read_fixed(&mut self) -> Option<f32> {
+    /// Read the given number of bytes.
+    pub fn read_bytes(&mut self, len: usize) -> Result<&[u8], AssetError> {
+        if self.remaining() < len {
+            return Err(self.error(AssetErrorKind::Truncated));
+        }
+        let start = self.index;
+        self.index += len;
+        Ok(&self.contents[start..self.index])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, AssetError> {
         let lo = self.read_byte()?;
         let hi = self.read_byte()?;
-        Some(f32::from(i16::from(lo) | (i16::from(hi) << 8)) / 256.0)
+        Ok(u16::from_le_bytes([lo, hi]))
     }
 
-    pub fn read_vector(&mut self) -> Option<Vector> {
+    pub fn read_u32(&mut self) -> Result<u32, AssetError> {
+        let lo = self.read_u16()?;
+        let hi = self.read_u16()?;
+        Ok(u32::from(lo) | (u32::from(hi) << 16))
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32, AssetError> {
+        Ok(f32::from_bits(self.read_u32()?))
+    }
+
+    /// Read a string preceded by its length in bytes as a u16.
+    pub fn read_string(&mut self) -> Result<String, AssetError> {
+        let len = usize::from(self.read_u16()?);
+        let offset = self.index;
+        let bytes = self.read_bytes(len)?.to_vec();
+        String::from_utf8(bytes).map_err(|e| {
+            self.error_at(
+                offset + e.utf8_error().valid_up_to(),
+                AssetErrorKind::InvalidText,
+            )
+        })
+    }
+
+    pub fn read_fixed(&mut self) -> Result<f32, AssetError> {
+        let lo = self.read_byte()?;
+        let hi = self.read_byte()?;
+        Ok(f32::from(i16::from(lo) | (i16::from(hi) << 8)) / 256.0)
+    }
+
+    pub fn read_vector(&mut self) -> Result<Vector, AssetError> {
         let x = self.read_fixed()?;
         let y = self.read_fixed()?;
         let z = self.read_fixed()?;
-        Some(Vector::new(x, y, z))
+        Ok(Vector::new(x, y, z))
+    }
+}
+
+/// Watches the content directory for assets that change while the game is
+/// running.
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+pub struct ContentWatcher {
+    /// When each asset was last modified.
+    modified: HashMap<String, SystemTime>,
+    /// When the content directory was last checked.
+    last_check: Instant,
+}
+
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+impl ContentWatcher {
+    /// How often to check for changes.
+    const INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Start watching. Assets that already exist are not reported as changed.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            modified: Self::scan(),
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Find when each asset in the content directory was last modified.
+    fn scan() -> HashMap<String, SystemTime> {
+        let mut modified = HashMap::new();
+        if let Ok(dir) = std::fs::read_dir(content_dir()) {
+            for entry in dir.flatten() {
+                let time = entry.metadata().and_then(|metadata| metadata.modified());
+                if let (Ok(name), Ok(time)) = (entry.file_name().into_string(), time) {
+                    modified.insert(name, time);
+                }
+            }
+        }
+        modified
+    }
+
+    /// Returns the names of the assets that were added, modified or removed
+    /// since the last check. Checks at most every so often, returning nothing
+    /// between.
+    pub fn changes(&mut self) -> Vec<String> {
+        if self.last_check.elapsed() < Self::INTERVAL {
+            return vec![];
+        }
+        self.last_check = Instant::now();
+        let modified = Self::scan();
+        let mut changed: Vec<String> = modified
+            .iter()
+            .filter(|(name, time)| self.modified.get(*name) != Some(time))
+            .map(|(name, _)| name.clone())
+            // removed assets fall back to the built-in ones
+            .chain(
+                self.modified
+                    .keys()
+                    .filter(|name| !modified.contains_key(*name))
+                    .cloned(),
+            )
+            .collect();
+        changed.sort();
+        self.modified = modified;
+        changed
     }
 }
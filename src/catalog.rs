@@ -0,0 +1,93 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Finds the courses that can be raced on.
+
+use crate::{
+    assets::{Asset, AssetError},
+    course::{Course, CourseMeta},
+    spline::Spline,
+};
+
+/// The course used when none has been chosen.
+pub const DEFAULT_COURSE: &str = "course_test1.bin";
+
+/// The prefix of course assets.
+const COURSE_PREFIX: &str = "course_";
+
+/// The extension of course assets.
+const COURSE_EXTENSION: &str = ".bin";
+
+/// A course that can be chosen.
+pub struct CourseEntry {
+    /// The file the course is loaded from, used to identify it.
+    pub file: String,
+    /// The name shown to the player.
+    pub name: String,
+    /// Information stored in the course.
+    pub meta: CourseMeta,
+    /// The loaded course.
+    pub spline: Spline,
+}
+
+impl CourseEntry {
+    fn load(file: String) -> Result<Self, AssetError> {
+        let course = Course::load(&mut Asset::load(&file)?)?;
+        let spline = Spline::new(&course);
+        // name the course after its file if it isn't named
+        let name = course.meta.name.clone().unwrap_or_else(|| {
+            let name = file.strip_suffix(COURSE_EXTENSION).unwrap_or(&file);
+            let name = name.strip_prefix(COURSE_PREFIX).unwrap_or(name);
+            name.replace('_', " ")
+        });
+        Ok(Self {
+            file,
+            name,
+            meta: course.meta,
+            spline,
+        })
+    }
+}
+
+/// Every course that was found, in order of file name.
+pub struct Catalog {
+    pub courses: Vec<CourseEntry>,
+}
+
+impl Catalog {
+    /// Find and load every course, including custom ones. Courses that fail
+    /// to load are skipped.
+    #[must_use]
+    pub fn scan() -> Self {
+        let courses = Asset::names()
+            .into_iter()
+            .filter(|name| name.starts_with(COURSE_PREFIX) && name.ends_with(COURSE_EXTENSION))
+            .filter_map(|file| match CourseEntry::load(file) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    eprintln!("failed to load course: {e}");
+                    None
+                }
+            })
+            .collect();
+        Self { courses }
+    }
+}
+
+/// Load a course by file name.
+pub fn load_course(file: &str) -> Result<Spline, AssetError> {
+    Spline::load(&mut Asset::load(file)?)
+}
@@ -0,0 +1,911 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The course file format.
+//!
+//! Version 1 courses are a point count byte followed by each point, stored as
+//! an 8.8 fixed-point position and a tilt byte.
+//!
+//! Version 2 courses start with the magic "CDXC" and a version byte, followed
+//! by chunks. Each chunk has a four-byte tag, its length as a u32, and then
+//! its contents. Chunks that aren't recognized are skipped, so that new ones
+//! can be added without breaking older versions of the game.
+//!
+//! - `META` holds the name, author, and lap count. Empty strings and a lap
+//!   count of zero mean the value is unset.
+//! - `PNTS` holds the point count as a u16, followed by each point's position
+//!   and tilt in radians as f32s.
+//! - `WDTH` holds the point count as a u16, followed by the width of the track
+//!   at each point as f32s. Courses without it use the default width.
+//! - `SURF` holds the surface zone count as a u16, followed by each zone's
+//!   kind as a u8, and its start and end offsets along the track and its left
+//!   and right extents as f32s. Extents are relative to the radius of the
+//!   track, from -1 at the left edge to 1 at the right edge. Zones of unknown
+//!   kinds are ignored.
+//! - `GAPS` holds the gap count as a u16, followed by each gap's start and end
+//!   offsets along the track as f32s.
+//! - `TUBE` holds tube sections, where the track wraps all the way around, in
+//!   the same format as `GAPS`.
+//! - `OPEN` has no contents, and makes the course run from its first point
+//!   to its last instead of looping back around.
+//! - `BRCH` holds the branch count as a u16, followed by each branch's fork
+//!   and merge offsets along the main track as f32s, its point count as a u16,
+//!   and each point's position, tilt, and width as f32s. The points of a
+//!   branch are the ones between where it leaves the main track and where it
+//!   joins it again.
+//!
+//! All numbers are little-endian, and strings are UTF-8 preceded by their
+//! length as a u16.
+
+use std::f32::consts::TAU;
+
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    assets::{Asset, AssetError, AssetErrorKind},
+    linalg::{Length, Vector},
+    spline::Spline,
+};
+
+/// The magic at the start of versioned courses.
+const COURSE_MAGIC: &[u8; 4] = b"CDXC";
+
+/// The newest version of the course format.
+const COURSE_VERSION: u8 = 2;
+
+/// A control point of a course.
+#[derive(Clone, Copy)]
+pub struct CoursePoint {
+    /// The position of the point.
+    pub position: Vector,
+    /// The tilt of the track at the point, in radians.
+    pub tilt: f32,
+    /// The width of the track at the point.
+    pub width: f32,
+}
+
+/// The kinds of track surface that behave differently from the rest.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceKind {
+    /// Speeds vehicles up past their top speed.
+    Boost,
+    /// Lowers the top speed of vehicles.
+    Rough,
+    /// Makes vehicles drift more.
+    Ice,
+}
+
+impl SurfaceKind {
+    /// Get the kind stored in a course file as the given byte.
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Boost),
+            1 => Some(Self::Rough),
+            2 => Some(Self::Ice),
+            _ => None,
+        }
+    }
+
+    /// Get the byte the kind is stored as in a course file.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Boost => 0,
+            Self::Rough => 1,
+            Self::Ice => 2,
+        }
+    }
+}
+
+/// An area of the track with a different surface.
+#[derive(Clone, Copy)]
+pub struct Surface {
+    /// What the surface does.
+    pub kind: SurfaceKind,
+    /// The offset along the track where the zone starts.
+    pub start: f32,
+    /// The offset along the track where the zone ends. If this is before the
+    /// start, the zone crosses the finish line.
+    pub end: f32,
+    /// The left extent of the zone, relative to the radius of the track.
+    pub left: f32,
+    /// The right extent of the zone, relative to the radius of the track.
+    pub right: f32,
+}
+
+/// A stretch of the track between two offsets, such as a gap or a tube.
+#[derive(Clone, Copy)]
+pub struct Section {
+    /// The offset along the track where the section starts.
+    pub start: f32,
+    /// The offset along the track where the section ends. If this is before
+    /// the start, the section crosses the finish line.
+    pub end: f32,
+}
+
+/// An alternate route that leaves the main track and joins it again.
+#[derive(Clone)]
+pub struct CourseBranch {
+    /// The offset along the main track where the branch leaves it.
+    pub fork: f32,
+    /// The offset along the main track where the branch joins it again.
+    pub merge: f32,
+    /// The points between the fork and the merge.
+    pub points: Vec<CoursePoint>,
+}
+
+/// Information about a course that doesn't affect its shape.
+#[derive(Clone, Default)]
+pub struct CourseMeta {
+    /// The name of the course.
+    pub name: Option<String>,
+    /// Who made the course.
+    pub author: Option<String>,
+    /// The number of laps to race, if the course suggests one.
+    pub laps: Option<u16>,
+}
+
+/// A course as stored in a file.
+#[derive(Clone)]
+pub struct Course {
+    pub meta: CourseMeta,
+    pub points: Vec<CoursePoint>,
+    pub surfaces: Vec<Surface>,
+    /// The sections with no floor.
+    pub gaps: Vec<Section>,
+    /// The sections where the track is a tube.
+    pub tubes: Vec<Section>,
+    pub branches: Vec<CourseBranch>,
+    /// If true, the last point connects back to the first.
+    pub closed: bool,
+}
+
+impl Course {
+    /// The fewest points a course can have.
+    pub const MIN_POINTS: usize = 3;
+    /// The narrowest a course can be at any point.
+    pub const MIN_WIDTH: f32 = 1.0;
+    /// The width of courses that don't specify one.
+    pub const DEFAULT_WIDTH: f32 = 2.0 * Spline::TRACK_RADIUS;
+
+    /// Check if any point is in the same place as the next one it connects
+    /// to, which a spline can't be built through.
+    #[must_use]
+    pub fn has_overlapping_points(&self) -> bool {
+        let len = self.points.len();
+        // the ends of an open course never meet
+        let connections = if self.closed { len } else { len - 1 };
+        (0..connections).any(|i| {
+            let next = &self.points[(i + 1) % len];
+            self.points[i].position.dist(next.position) <= f32::EPSILON
+        })
+    }
+
+    /// Read a course of any version.
+    pub fn load(asset: &mut Asset) -> Result<Self, AssetError> {
+        let has_magic = asset.remaining() > COURSE_MAGIC.len()
+            && asset.read_bytes(COURSE_MAGIC.len())? == COURSE_MAGIC;
+        if !has_magic {
+            // courses without a header are version 1
+            asset.seek(0);
+            return Self::load_v1(asset);
+        }
+        let version = asset.read_byte()?;
+        if version != COURSE_VERSION {
+            return Err(asset.error_at(
+                asset.offset() - 1,
+                AssetErrorKind::UnsupportedVersion(version),
+            ));
+        }
+        Self::load_v2(asset)
+    }
+
+    /// Write the course in the newest version of the course format.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(COURSE_MAGIC)?;
+        writer.write_all(&[COURSE_VERSION])?;
+        // unset values are stored as empty
+        let mut meta = vec![];
+        write_string(&mut meta, self.meta.name.as_deref().unwrap_or_default())?;
+        write_string(&mut meta, self.meta.author.as_deref().unwrap_or_default())?;
+        meta.write_all(&self.meta.laps.unwrap_or(0).to_le_bytes())?;
+        write_chunk(writer, b"META", &meta)?;
+        let mut points = vec![];
+        let mut widths = vec![];
+        write_count(&mut points, self.points.len())?;
+        write_count(&mut widths, self.points.len())?;
+        for point in &self.points {
+            let position = point.position;
+            write_f32s(
+                &mut points,
+                &[position.x, position.y, position.z, point.tilt],
+            )?;
+            write_f32s(&mut widths, &[point.width])?;
+        }
+        write_chunk(writer, b"PNTS", &points)?;
+        write_chunk(writer, b"WDTH", &widths)?;
+        if !self.surfaces.is_empty() {
+            let mut surfaces = vec![];
+            write_count(&mut surfaces, self.surfaces.len())?;
+            for surface in &self.surfaces {
+                surfaces.write_all(&[surface.kind.to_byte()])?;
+                write_f32s(
+                    &mut surfaces,
+                    &[surface.start, surface.end, surface.left, surface.right],
+                )?;
+            }
+            write_chunk(writer, b"SURF", &surfaces)?;
+        }
+        if !self.gaps.is_empty() {
+            write_chunk(writer, b"GAPS", &sections_chunk(&self.gaps)?)?;
+        }
+        if !self.tubes.is_empty() {
+            write_chunk(writer, b"TUBE", &sections_chunk(&self.tubes)?)?;
+        }
+        if !self.branches.is_empty() {
+            let mut branches = vec![];
+            write_count(&mut branches, self.branches.len())?;
+            for branch in &self.branches {
+                write_f32s(&mut branches, &[branch.fork, branch.merge])?;
+                write_count(&mut branches, branch.points.len())?;
+                for point in &branch.points {
+                    let position = point.position;
+                    write_f32s(
+                        &mut branches,
+                        &[position.x, position.y, position.z, point.tilt, point.width],
+                    )?;
+                }
+            }
+            write_chunk(writer, b"BRCH", &branches)?;
+        }
+        if !self.closed {
+            write_chunk(writer, b"OPEN", &[])?;
+        }
+        Ok(())
+    }
+
+    /// Save the course to a file.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    fn load_v1(asset: &mut Asset) -> Result<Self, AssetError> {
+        let num_points = asset.read_byte()?;
+        let mut points = vec![];
+        let mut offsets = vec![];
+        for _ in 0..num_points {
+            offsets.push(asset.offset());
+            let position = asset.read_vector()?;
+            let tilt = (f32::from(asset.read_byte()?) / 256.0) * TAU;
+            points.push(CoursePoint {
+                position,
+                tilt,
+                width: Self::DEFAULT_WIDTH,
+            });
+        }
+        check_points(asset, &points, &offsets, true)?;
+        Ok(Self {
+            meta: CourseMeta::default(),
+            points,
+            surfaces: vec![],
+            gaps: vec![],
+            tubes: vec![],
+            branches: vec![],
+            closed: true,
+        })
+    }
+
+    fn load_v2(asset: &mut Asset) -> Result<Self, AssetError> {
+        let mut meta = CourseMeta::default();
+        let mut points = None;
+        let mut widths = None;
+        let mut surfaces = vec![];
+        let mut gaps = vec![];
+        let mut tubes = vec![];
+        let mut branches = vec![];
+        let mut closed = true;
+        while asset.remaining() > 0 {
+            let tag = asset.read_bytes(4)?;
+            let tag = String::from_utf8_lossy(tag).into_owned();
+            let len = asset.read_u32()? as usize;
+            let start = asset.offset();
+            if asset.remaining() < len {
+                return Err(asset.error(AssetErrorKind::Truncated));
+            }
+            let end = start + len;
+            match tag.as_str() {
+                "META" => meta = read_meta(asset)?,
+                "PNTS" => points = Some(read_points(asset)?),
+                "WDTH" => widths = Some(read_widths(asset, start)?),
+                "SURF" => surfaces = read_surfaces(asset)?,
+                "GAPS" => gaps = read_sections(asset)?,
+                "TUBE" => tubes = read_sections(asset)?,
+                "BRCH" => branches = read_branches(asset)?,
+                "OPEN" => closed = false,
+                // skip chunks from newer versions
+                _ => {}
+            }
+            if asset.offset() > end {
+                return Err(asset.error_at(start, AssetErrorKind::MalformedChunk(tag)));
+            }
+            asset.seek(end);
+        }
+        let (mut points, offsets) = match points {
+            Some(points) => points,
+            None => {
+                return Err(asset.error(AssetErrorKind::MissingChunk(String::from("PNTS"))));
+            }
+        };
+        check_points(asset, &points, &offsets, closed)?;
+        if let Some((offset, widths)) = widths {
+            if widths.len() != points.len() {
+                return Err(
+                    asset.error_at(offset, AssetErrorKind::MalformedChunk(String::from("WDTH")))
+                );
+            }
+            for (point, width) in points.iter_mut().zip(widths) {
+                point.width = width;
+            }
+        }
+        Ok(Self {
+            meta,
+            points,
+            surfaces,
+            gaps,
+            tubes,
+            branches,
+            closed,
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Write a chunk's tag and length, followed by its contents.
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+fn write_chunk(writer: &mut impl Write, tag: &[u8; 4], contents: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(contents.len()).map_err(|_| invalid("chunk too long"))?;
+    writer.write_all(tag)?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(contents)
+}
+
+/// Write the number of items that follow as a u16.
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+fn write_count(writer: &mut impl Write, count: usize) -> io::Result<()> {
+    let count = u16::try_from(count).map_err(|_| invalid("too many items"))?;
+    writer.write_all(&count.to_le_bytes())
+}
+
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+fn write_f32s(writer: &mut impl Write, values: &[f32]) -> io::Result<()> {
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+fn write_string(writer: &mut impl Write, s: &str) -> io::Result<()> {
+    let len = u16::try_from(s.len()).map_err(|_| invalid("string too long"))?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(s.as_bytes())
+}
+
+/// Build the contents of a GAPS or TUBE chunk.
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+fn sections_chunk(sections: &[Section]) -> io::Result<Vec<u8>> {
+    let mut contents = vec![];
+    write_count(&mut contents, sections.len())?;
+    for section in sections {
+        write_f32s(&mut contents, &[section.start, section.end])?;
+    }
+    Ok(contents)
+}
+
+/// Read the contents of a META chunk.
+fn read_meta(asset: &mut Asset) -> Result<CourseMeta, AssetError> {
+    let name = asset.read_string()?;
+    let author = asset.read_string()?;
+    let laps = asset.read_u16()?;
+    Ok(CourseMeta {
+        name: (!name.is_empty()).then_some(name),
+        author: (!author.is_empty()).then_some(author),
+        laps: (laps != 0).then_some(laps),
+    })
+}
+
+/// Read the contents of a PNTS chunk, along with the offset of each point.
+fn read_points(asset: &mut Asset) -> Result<(Vec<CoursePoint>, Vec<usize>), AssetError> {
+    let num_points = asset.read_u16()?;
+    let mut points = vec![];
+    let mut offsets = vec![];
+    for _ in 0..num_points {
+        offsets.push(asset.offset());
+        let x = asset.read_f32()?;
+        let y = asset.read_f32()?;
+        let z = asset.read_f32()?;
+        let tilt = asset.read_f32()?;
+        points.push(CoursePoint {
+            position: Vector::new(x, y, z),
+            tilt: tilt.rem_euclid(TAU),
+            width: Course::DEFAULT_WIDTH,
+        });
+    }
+    Ok((points, offsets))
+}
+
+/// Read the contents of a WDTH chunk that starts at the given offset, keeping
+/// the offset for reporting a mismatched point count.
+fn read_widths(asset: &mut Asset, start: usize) -> Result<(usize, Vec<f32>), AssetError> {
+    let num_widths = asset.read_u16()?;
+    let mut widths = vec![];
+    for _ in 0..num_widths {
+        let offset = asset.offset();
+        let width = asset.read_f32()?;
+        if !width.is_finite() || width < Course::MIN_WIDTH {
+            return Err(asset.error_at(offset, AssetErrorKind::DegenerateGeometry));
+        }
+        widths.push(width);
+    }
+    Ok((start, widths))
+}
+
+/// Read the contents of a SURF chunk.
+fn read_surfaces(asset: &mut Asset) -> Result<Vec<Surface>, AssetError> {
+    let num_surfaces = asset.read_u16()?;
+    let mut surfaces = vec![];
+    for _ in 0..num_surfaces {
+        let offset = asset.offset();
+        let kind = asset.read_byte()?;
+        let start = asset.read_f32()?;
+        let end = asset.read_f32()?;
+        let left = asset.read_f32()?;
+        let right = asset.read_f32()?;
+        let valid = [start, end, left, right].iter().all(|n| n.is_finite())
+            && (-1.0..=1.0).contains(&left)
+            && (-1.0..=1.0).contains(&right)
+            && left < right;
+        if !valid {
+            return Err(asset.error_at(offset, AssetErrorKind::DegenerateGeometry));
+        }
+        // zones from newer versions are skipped
+        if let Some(kind) = SurfaceKind::from_byte(kind) {
+            surfaces.push(Surface {
+                kind,
+                start,
+                end,
+                left,
+                right,
+            });
+        }
+    }
+    Ok(surfaces)
+}
+
+/// Read the contents of a GAPS or TUBE chunk.
+fn read_sections(asset: &mut Asset) -> Result<Vec<Section>, AssetError> {
+    let num_sections = asset.read_u16()?;
+    let mut sections = vec![];
+    for _ in 0..num_sections {
+        let offset = asset.offset();
+        let start = asset.read_f32()?;
+        let end = asset.read_f32()?;
+        if !start.is_finite() || !end.is_finite() {
+            return Err(asset.error_at(offset, AssetErrorKind::DegenerateGeometry));
+        }
+        sections.push(Section { start, end });
+    }
+    Ok(sections)
+}
+
+/// Read the contents of a BRCH chunk.
+fn read_branches(asset: &mut Asset) -> Result<Vec<CourseBranch>, AssetError> {
+    let num_branches = asset.read_u16()?;
+    let mut branches = vec![];
+    for _ in 0..num_branches {
+        let offset = asset.offset();
+        let fork = asset.read_f32()?;
+        let merge = asset.read_f32()?;
+        let num_points = asset.read_u16()?;
+        if !fork.is_finite() || !merge.is_finite() || num_points == 0 {
+            return Err(asset.error_at(offset, AssetErrorKind::DegenerateGeometry));
+        }
+        let mut points: Vec<CoursePoint> = vec![];
+        for _ in 0..num_points {
+            let offset = asset.offset();
+            let x = asset.read_f32()?;
+            let y = asset.read_f32()?;
+            let z = asset.read_f32()?;
+            let tilt = asset.read_f32()?;
+            let width = asset.read_f32()?;
+            let position = Vector::new(x, y, z);
+            // points in the same place would divide by zero
+            let apart = match points.last() {
+                Some(last) => last.position.dist(position) > f32::EPSILON,
+                None => true,
+            };
+            let valid = [x, y, z, tilt, width].iter().all(|n| n.is_finite())
+                && width >= Course::MIN_WIDTH
+                && apart;
+            if !valid {
+                return Err(asset.error_at(offset, AssetErrorKind::DegenerateGeometry));
+            }
+            points.push(CoursePoint {
+                position,
+                tilt: tilt.rem_euclid(TAU),
+                width,
+            });
+        }
+        branches.push(CourseBranch {
+            fork,
+            merge,
+            points,
+        });
+    }
+    Ok(branches)
+}
+
+/// Check that a spline can be built from the points, given the offset each
+/// point was read from and whether the last point connects to the first.
+fn check_points(
+    asset: &Asset,
+    points: &[CoursePoint],
+    offsets: &[usize],
+    closed: bool,
+) -> Result<(), AssetError> {
+    if points.len() < Course::MIN_POINTS {
+        return Err(asset.error_at(0, AssetErrorKind::TooFewPoints));
+    }
+    for (i, point) in points.iter().enumerate() {
+        let next = &points[(i + 1) % points.len()];
+        let finite = [
+            point.position.x,
+            point.position.y,
+            point.position.z,
+            point.tilt,
+        ]
+        .iter()
+        .all(|n| n.is_finite());
+        // points in the same place would divide by zero, but the ends of an
+        // open course never meet
+        let connected = closed || i + 1 < points.len();
+        if !finite || (connected && point.position.dist(next.position) <= f32::EPSILON) {
+            let offset = offsets[(i + 1) % offsets.len()];
+            return Err(asset.error_at(offset, AssetErrorKind::DegenerateGeometry));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A triangle of points, each as x, y, z and tilt.
+    const TRIANGLE: [[f32; 4]; 3] = [
+        [0.0, 0.0, 0.0, 0.0],
+        [10.0, 0.0, 0.0, 0.0],
+        [0.0, 0.0, 10.0, 0.0],
+    ];
+
+    fn chunk(tag: &[u8; 4], contents: &[u8]) -> Vec<u8> {
+        let mut bytes = tag.to_vec();
+        bytes.extend((contents.len() as u32).to_le_bytes());
+        bytes.extend(contents);
+        bytes
+    }
+
+    fn file(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = COURSE_MAGIC.to_vec();
+        bytes.push(COURSE_VERSION);
+        for chunk in chunks {
+            bytes.extend(chunk);
+        }
+        bytes
+    }
+
+    fn count(n: usize) -> Vec<u8> {
+        (n as u16).to_le_bytes().to_vec()
+    }
+
+    fn floats(values: &[f32]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    fn points(points: &[[f32; 4]]) -> Vec<u8> {
+        let mut bytes = count(points.len());
+        for point in points {
+            bytes.extend(floats(point));
+        }
+        chunk(b"PNTS", &bytes)
+    }
+
+    fn sections(tag: &[u8; 4], sections: &[(f32, f32)]) -> Vec<u8> {
+        let mut bytes = count(sections.len());
+        for &(start, end) in sections {
+            bytes.extend(floats(&[start, end]));
+        }
+        chunk(tag, &bytes)
+    }
+
+    fn load(bytes: Vec<u8>) -> Result<Course, AssetError> {
+        Course::load(&mut Asset::from_bytes("test", bytes))
+    }
+
+    fn load_err(bytes: Vec<u8>) -> AssetError {
+        match load(bytes) {
+            Ok(_) => panic!("course should not load"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn v1_fallback() {
+        let mut bytes = vec![3];
+        for (x, z, tilt) in [(0, 0, 0), (10, 0, 64), (0, 10, 128)] {
+            for value in [x * 256_i16, 0, z * 256] {
+                bytes.extend(value.to_le_bytes());
+            }
+            bytes.push(tilt);
+        }
+        let course = load(bytes).unwrap();
+        assert_eq!(course.points.len(), 3);
+        assert_eq!(course.points[1].position.x, 10.0);
+        assert_eq!(course.points[2].position.z, 10.0);
+        assert!((course.points[1].tilt - TAU / 4.0).abs() < 1e-6);
+        assert_eq!(course.points[0].width, Course::DEFAULT_WIDTH);
+        assert!(course.closed);
+        assert!(course.meta.name.is_none());
+    }
+
+    #[test]
+    fn v2_chunks() {
+        let mut meta = vec![];
+        for s in ["Loop", "Someone"] {
+            meta.extend(count(s.len()));
+            meta.extend(s.as_bytes());
+        }
+        meta.extend(5_u16.to_le_bytes());
+        let course = load(file(&[chunk(b"META", &meta), points(&TRIANGLE)])).unwrap();
+        assert_eq!(course.meta.name.as_deref(), Some("Loop"));
+        assert_eq!(course.meta.author.as_deref(), Some("Someone"));
+        assert_eq!(course.meta.laps, Some(5));
+        assert_eq!(course.points.len(), 3);
+        assert!(course.closed);
+    }
+
+    #[test]
+    fn unknown_chunk_skipped() {
+        let course = load(file(&[
+            chunk(b"XTRA", &[1, 2, 3, 4, 5]),
+            points(&TRIANGLE),
+            chunk(b"NEWR", &[]),
+        ]))
+        .unwrap();
+        assert_eq!(course.points.len(), 3);
+    }
+
+    #[test]
+    fn truncated_chunk() {
+        let mut bytes = file(&[points(&TRIANGLE)]);
+        bytes.pop();
+        let e = load_err(bytes);
+        assert_eq!(e.kind, AssetErrorKind::Truncated);
+        // reported at the start of the chunk's contents
+        assert_eq!(e.offset, 13);
+    }
+
+    #[test]
+    fn chunk_read_past_its_length() {
+        // the length claims less than the points need
+        let mut bytes = points(&TRIANGLE);
+        bytes[4..8].copy_from_slice(&4_u32.to_le_bytes());
+        let e = load_err(file(&[bytes]));
+        assert_eq!(e.kind, AssetErrorKind::MalformedChunk(String::from("PNTS")));
+        assert_eq!(e.offset, 13);
+    }
+
+    #[test]
+    fn missing_points() {
+        let e = load_err(file(&[]));
+        assert_eq!(e.kind, AssetErrorKind::MissingChunk(String::from("PNTS")));
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let mut bytes = file(&[points(&TRIANGLE)]);
+        bytes[4] = COURSE_VERSION + 1;
+        let e = load_err(bytes);
+        assert_eq!(
+            e.kind,
+            AssetErrorKind::UnsupportedVersion(COURSE_VERSION + 1)
+        );
+        assert_eq!(e.offset, 4);
+    }
+
+    #[test]
+    fn too_few_points() {
+        let e = load_err(file(&[points(&TRIANGLE[..2])]));
+        assert_eq!(e.kind, AssetErrorKind::TooFewPoints);
+    }
+
+    #[test]
+    fn degenerate_point() {
+        let mut repeated = TRIANGLE;
+        repeated[2] = repeated[1];
+        let e = load_err(file(&[points(&repeated)]));
+        assert_eq!(e.kind, AssetErrorKind::DegenerateGeometry);
+        // reported at the repeated point
+        assert_eq!(e.offset, 13 + 2 + 16 * 2);
+
+        let mut infinite = TRIANGLE;
+        infinite[0][3] = f32::NAN;
+        let e = load_err(file(&[points(&infinite)]));
+        assert_eq!(e.kind, AssetErrorKind::DegenerateGeometry);
+    }
+
+    #[test]
+    fn open_ends_may_meet() {
+        let mut looped = TRIANGLE.to_vec();
+        looped.push(TRIANGLE[0]);
+        let e = load_err(file(&[points(&looped)]));
+        assert_eq!(e.kind, AssetErrorKind::DegenerateGeometry);
+        let course = load(file(&[points(&looped), chunk(b"OPEN", &[])])).unwrap();
+        assert!(!course.closed);
+    }
+
+    #[test]
+    fn widths() {
+        let mut widths = count(3);
+        widths.extend(floats(&[2.0, 3.0, 4.0]));
+        let course = load(file(&[points(&TRIANGLE), chunk(b"WDTH", &widths)])).unwrap();
+        assert_eq!(course.points[1].width, 3.0);
+
+        let mut short = count(2);
+        short.extend(floats(&[2.0, 3.0]));
+        let e = load_err(file(&[points(&TRIANGLE), chunk(b"WDTH", &short)]));
+        assert_eq!(e.kind, AssetErrorKind::MalformedChunk(String::from("WDTH")));
+
+        let mut narrow = count(3);
+        narrow.extend(floats(&[2.0, Course::MIN_WIDTH * 0.5, 4.0]));
+        let e = load_err(file(&[points(&TRIANGLE), chunk(b"WDTH", &narrow)]));
+        assert_eq!(e.kind, AssetErrorKind::DegenerateGeometry);
+    }
+
+    #[test]
+    fn surfaces() {
+        let mut surfaces = count(2);
+        surfaces.push(0);
+        surfaces.extend(floats(&[1.0, 2.0, -1.0, 0.0]));
+        // a kind from a newer version
+        surfaces.push(200);
+        surfaces.extend(floats(&[1.0, 2.0, -1.0, 0.0]));
+        let course = load(file(&[points(&TRIANGLE), chunk(b"SURF", &surfaces)])).unwrap();
+        assert_eq!(course.surfaces.len(), 1);
+        assert!(course.surfaces[0].kind == SurfaceKind::Boost);
+
+        let mut backwards = count(1);
+        backwards.push(1);
+        backwards.extend(floats(&[1.0, 2.0, 0.5, -0.5]));
+        let e = load_err(file(&[points(&TRIANGLE), chunk(b"SURF", &backwards)]));
+        assert_eq!(e.kind, AssetErrorKind::DegenerateGeometry);
+    }
+
+    #[test]
+    fn gaps_and_tubes() {
+        let course = load(file(&[
+            points(&TRIANGLE),
+            sections(b"GAPS", &[(1.0, 2.0)]),
+            sections(b"TUBE", &[(3.0, 5.0), (8.0, 1.0)]),
+        ]))
+        .unwrap();
+        assert_eq!(course.gaps.len(), 1);
+        assert_eq!(course.tubes.len(), 2);
+        assert_eq!(course.tubes[1].start, 8.0);
+
+        let e = load_err(file(&[
+            points(&TRIANGLE),
+            sections(b"GAPS", &[(f32::INFINITY, 2.0)]),
+        ]));
+        assert_eq!(e.kind, AssetErrorKind::DegenerateGeometry);
+    }
+
+    #[test]
+    fn branches() {
+        let mut branch = count(1);
+        branch.extend(floats(&[1.0, 5.0]));
+        branch.extend(count(2));
+        branch.extend(floats(&[5.0, 0.0, 5.0, 0.0, 4.0]));
+        branch.extend(floats(&[6.0, 0.0, 6.0, 0.0, 4.0]));
+        let course = load(file(&[points(&TRIANGLE), chunk(b"BRCH", &branch)])).unwrap();
+        assert_eq!(course.branches.len(), 1);
+        assert_eq!(course.branches[0].points.len(), 2);
+        assert_eq!(course.branches[0].merge, 5.0);
+
+        let mut repeated = count(1);
+        repeated.extend(floats(&[1.0, 5.0]));
+        repeated.extend(count(2));
+        repeated.extend(floats(&[5.0, 0.0, 5.0, 0.0, 4.0]));
+        repeated.extend(floats(&[5.0, 0.0, 5.0, 0.0, 4.0]));
+        let e = load_err(file(&[points(&TRIANGLE), chunk(b"BRCH", &repeated)]));
+        assert_eq!(e.kind, AssetErrorKind::DegenerateGeometry);
+    }
+
+    #[test]
+    fn write_round_trip() {
+        let mut bytes = file(&[
+            points(&TRIANGLE),
+            sections(b"GAPS", &[(1.0, 2.0)]),
+            sections(b"TUBE", &[(8.0, 1.0)]),
+            chunk(b"OPEN", &[]),
+        ]);
+        let mut course = load(bytes).unwrap();
+        course.meta.name = Some(String::from("Loop"));
+        course.meta.laps = Some(2);
+        course.points[1].width = 5.0;
+        course.surfaces.push(Surface {
+            kind: SurfaceKind::Ice,
+            start: 2.0,
+            end: 3.0,
+            left: -0.5,
+            right: 0.5,
+        });
+        course.branches.push(CourseBranch {
+            fork: 1.0,
+            merge: 4.0,
+            points: TRIANGLE[1..]
+                .iter()
+                .map(|&[x, y, z, tilt]| CoursePoint {
+                    position: Vector::new(x, y + 1.0, z),
+                    tilt,
+                    width: 3.0,
+                })
+                .collect(),
+        });
+        bytes = vec![];
+        course.write(&mut bytes).unwrap();
+        let loaded = load(bytes).unwrap();
+        assert_eq!(loaded.meta.name.as_deref(), Some("Loop"));
+        assert!(loaded.meta.author.is_none());
+        assert_eq!(loaded.meta.laps, Some(2));
+        assert_eq!(loaded.points.len(), 3);
+        assert_eq!(loaded.points[2].position.z, 10.0);
+        assert_eq!(loaded.points[1].width, 5.0);
+        assert!(loaded.surfaces[0].kind == SurfaceKind::Ice);
+        assert_eq!(loaded.surfaces[0].left, -0.5);
+        assert_eq!(loaded.gaps[0].end, 2.0);
+        assert_eq!(loaded.tubes[0].start, 8.0);
+        assert_eq!(loaded.branches[0].points[1].position.y, 1.0);
+        assert_eq!(loaded.branches[0].merge, 4.0);
+        assert!(!loaded.closed);
+    }
+}
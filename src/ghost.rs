@@ -0,0 +1,118 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::linalg::{Quat, Vector};
+
+/// The position and rotation of a vehicle on a single tick.
+pub type GhostFrame = (Vector, Quat);
+
+/// A recording of a lap that can be played back as a ghost.
+pub struct Ghost {
+    /// The lap time in ticks.
+    pub time: u32,
+    /// The transforms of the vehicle on each tick.
+    frames: Vec<GhostFrame>,
+}
+
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+const GHOST_MAGIC: &[u8; 4] = b"CDXG";
+
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+const GHOST_VERSION: u8 = 1;
+
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+    Ok(f32::from_bits(read_u32(reader)?))
+}
+
+impl Ghost {
+    #[must_use]
+    pub fn new(time: u32, frames: Vec<GhostFrame>) -> Self {
+        Self { time, frames }
+    }
+
+    /// Get the frame for the given tick of the lap, or None if past the end.
+    #[must_use]
+    pub fn frame(&self, tick: u32) -> Option<&GhostFrame> {
+        self.frames.get(usize::try_from(tick).ok()?)
+    }
+
+    /// Save the ghost to a file.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(GHOST_MAGIC)?;
+        writer.write_all(&[GHOST_VERSION])?;
+        writer.write_all(&self.time.to_le_bytes())?;
+        let num_frames = u32::try_from(self.frames.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "ghost too long"))?;
+        writer.write_all(&num_frames.to_le_bytes())?;
+        for (pos, rot) in &self.frames {
+            for v in [pos.x, pos.y, pos.z, rot.w, rot.x, rot.y, rot.z] {
+                writer.write_all(&v.to_le_bytes())?;
+            }
+        }
+        writer.flush()
+    }
+
+    /// Load a ghost from a file.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        let mut version = [0; 1];
+        reader.read_exact(&mut version)?;
+        if &magic != GHOST_MAGIC || version[0] != GHOST_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a supported ghost file",
+            ));
+        }
+        let time = read_u32(&mut reader)?;
+        let num_frames = read_u32(&mut reader)?;
+        let mut frames = vec![];
+        for _ in 0..num_frames {
+            let pos = Vector::new(
+                read_f32(&mut reader)?,
+                read_f32(&mut reader)?,
+                read_f32(&mut reader)?,
+            );
+            let rot = Quat::new(
+                read_f32(&mut reader)?,
+                read_f32(&mut reader)?,
+                read_f32(&mut reader)?,
+                read_f32(&mut reader)?,
+            );
+            frames.push((pos, rot));
+        }
+        Ok(Self { time, frames })
+    }
+}
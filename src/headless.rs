@@ -0,0 +1,143 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Runs the game without a platform, for automated testing of gameplay.
+//!
+//! A [`Simulation`] ticks the game modes exactly as the game thread would, but
+//! with scripted controls and no window, timer, or rendering.
+
+pub use crate::{
+    assets::AssetError,
+    linalg::Vector,
+    mode::race::{RaceSetup, VehicleSummary},
+    platform::{Buttons, Controls, PlayerControls, MAX_PLAYERS},
+};
+
+use crate::{
+    apply_controls, init_data,
+    mode::{race::RaceMode, GlobalGameData, Mode},
+    settings::Settings,
+    tick_mode,
+};
+
+/// A game running without a platform.
+pub struct Simulation {
+    /// The global game data.
+    data: GlobalGameData,
+    /// The current game mode. Only None while the mode is being ticked.
+    mode: Option<Box<dyn Mode>>,
+    /// The number of ticks run so far.
+    ticks: u32,
+}
+
+impl Simulation {
+    /// Start a simulation of a race. The race is loaded immediately, and ghosts
+    /// are not saved.
+    pub fn race(setup: RaceSetup) -> Result<Self, AssetError> {
+        let mut data = init_data(Settings::default());
+        data.garage.load_manifest()?;
+        data.seats
+            .replace((0..usize::from(setup.players)).collect());
+        let model = data.garage.find_id(&setup.model)?;
+        let mut race = RaceMode::load(model, setup)?;
+        race.disable_ghost();
+        Ok(Self {
+            data,
+            mode: Some(Box::new(race)),
+            ticks: 0,
+        })
+    }
+
+    /// Run a single tick with the given controls for the first player.
+    pub fn tick(&mut self, controls: Controls) {
+        let mut players = PlayerControls::default();
+        players[0] = controls;
+        self.tick_players(players);
+    }
+
+    /// Run a single tick with the given controls for every player.
+    pub fn tick_players(&mut self, players: PlayerControls) {
+        apply_controls(&mut self.data, players.map(Some));
+        if let Some(mode) = self.mode.take() {
+            self.mode = Some(tick_mode(mode, &mut self.data));
+        }
+        self.ticks += 1;
+    }
+
+    /// Run a number of ticks, asking the script for the controls on each tick.
+    /// The script is given the number of ticks run so far.
+    pub fn run<F>(&mut self, ticks: u32, mut script: F)
+    where
+        F: FnMut(u32) -> Controls,
+    {
+        for _ in 0..ticks {
+            let controls = script(self.ticks);
+            self.tick(controls);
+        }
+    }
+
+    /// Returns the number of ticks run so far.
+    #[must_use]
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// Set whether walls are enabled.
+    pub fn set_walls(&self, walls: bool) {
+        self.data.settings.borrow_mut().walls = walls;
+    }
+
+    fn current_race(&self) -> Option<&RaceMode> {
+        self.mode.as_ref()?.race()
+    }
+
+    /// Returns true if the game is still showing a race. This becomes false
+    /// once the race ends and the results are shown.
+    #[must_use]
+    pub fn is_racing(&self) -> bool {
+        self.current_race().is_some()
+    }
+
+    /// Returns the number of ticks since the race started, if still racing.
+    #[must_use]
+    pub fn race_time(&self) -> Option<u32> {
+        Some(self.current_race()?.time())
+    }
+
+    /// Returns a summary of every vehicle in the race, or an empty list if
+    /// the race is over.
+    #[must_use]
+    pub fn vehicles(&self) -> Vec<VehicleSummary> {
+        if let Some(race) = self.current_race() {
+            (0..race.num_vehicles())
+                .filter_map(|i| race.summary(i))
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    /// Returns the vehicle indices ordered by race position, or an empty list
+    /// if the race is over.
+    #[must_use]
+    pub fn standings(&self) -> Vec<usize> {
+        if let Some(race) = self.current_race() {
+            race.standings().to_vec()
+        } else {
+            vec![]
+        }
+    }
+}
@@ -16,26 +16,35 @@
 
 #![cfg_attr(target_os = "horizon", feature(allocator_api))]
 
-use mode::{title::TitleMode, GlobalGameData, Mode};
+#[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
+use assets::ContentWatcher;
+use mode::{error::ErrorMode, title::TitleMode, GlobalGameData, Mode};
 
 use render::{context::GenericBaseContext, Font};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
 mod assets;
+mod catalog;
+mod course;
+mod ghost;
+pub mod headless;
 mod linalg;
 mod mode;
 mod octree;
 mod platform;
 mod render;
+mod replay;
+mod settings;
 mod spline;
 mod timing;
 mod util;
 mod vehicle;
 
-use platform::{Buttons, Controls, Impl, Platform};
-
-const DEADZONE: f32 = 0.03;
+use platform::{
+    Bindings, Buttons, Controls, DisplayMode, Impl, Platform, RawInput, SlotControls, MAX_PLAYERS,
+};
+use settings::Settings;
 
 use crate::{render::graph::RenderGraph, timing::Timer};
 
@@ -81,21 +90,24 @@ impl GameUpdate {
                 graph.render(&self.font, &mut ctx);
                 ctx.finish();
             }
+            RenderUpdate::DisplayMode(mode) => platform.set_display_mode(mode),
+            RenderUpdate::Bindings(bindings) => platform.set_bindings(&bindings),
         }
         result
     }
 }
 
 /// Contains information sent from the render thread to the game thread.
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 struct PlatformUpdate {
-    controls: Controls,
+    controls: SlotControls,
+    last_input: Option<RawInput>,
     width: u16,
     height: u16,
     #[cfg(not(target_arch = "wasm32"))]
     should_run: bool,
     #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
-    mouse_state: sdl2::mouse::MouseState,
+    mouse_state: platform::MouseState,
     #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
     scroll_wheel: i32,
 }
@@ -104,6 +116,10 @@ struct PlatformUpdate {
 enum RenderUpdate {
     /// Here is a scene to draw.
     Graph(RenderGraph),
+    /// The window should be shown differently.
+    DisplayMode(DisplayMode),
+    /// The controls have been rebound.
+    Bindings(Bindings),
     /// Game has ended
     #[cfg(not(target_arch = "wasm32"))]
     End,
@@ -120,28 +136,40 @@ struct Game {
     update: GameUpdate,
     /// The last seen value of update.
     last_update: PlatformUpdate,
+    /// The display mode last sent to the platform.
+    display_mode: DisplayMode,
+    /// The bindings last sent to the platform.
+    bindings: Bindings,
+    /// Watches for custom assets to reload.
+    #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
+    watcher: ContentWatcher,
 }
 
 impl Game {
     #[must_use]
-    fn init(update: GameUpdate) -> Self {
+    fn init(update: GameUpdate, settings: Settings) -> Self {
         // let platform = platform::Impl::init(640, 480);
-        let mut data = GlobalGameData::default();
-        data.garage.load_hardcoded();
-        data.walls.set(true);
-
-        #[cfg(not(target_arch = "wasm32"))]
-        data.should_run.set(true);
-
+        let display_mode = settings.display_mode();
+        let bindings = settings.bindings.clone();
+        let mut data = init_data(settings);
+        // nothing can be raced without vehicles, so explain if they're missing
+        let mode: Box<dyn Mode> = match data.garage.load_manifest() {
+            Ok(()) => Box::new(TitleMode::new()),
+            Err(e) => Box::new(ErrorMode::asset(&e)),
+        };
         let last_update = update.send_update(RenderUpdate::Graph(RenderGraph::default()));
         let timer = Timer::new();
 
         Self {
             timer,
-            mode: Box::new(TitleMode),
+            mode,
             data,
             update,
             last_update,
+            display_mode,
+            bindings,
+            #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
+            watcher: ContentWatcher::new(),
         }
     }
 
@@ -151,43 +179,53 @@ impl Game {
     }
 
     fn update_controls(&mut self) {
-        // get controls
-        let mut controls = self.last_update.controls;
-        // apply deadzone
-        if controls.steering.abs() < DEADZONE {
-            controls.steering = 0.0;
-        }
-        // cancel out up/down and left/right
-        if controls.buttons.contains(Buttons::UP | Buttons::DOWN) {
-            controls.buttons &= !(Buttons::UP | Buttons::DOWN);
-        }
-        if controls.buttons.contains(Buttons::LEFT | Buttons::RIGHT) {
-            controls.buttons &= !(Buttons::LEFT | Buttons::RIGHT);
-        }
-        // determine which buttons were pressed
-        self.data.pressed = controls.buttons & !self.data.controls.buttons;
-        self.data.controls = controls;
+        apply_controls(&mut self.data, self.last_update.controls);
+        self.data.last_input = self.last_update.last_input.take();
         #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
         {
             self.data.mouse_state = self.last_update.mouse_state;
             self.data.scroll_wheel = self.last_update.scroll_wheel;
+            self.data.screen_size = (self.last_update.width, self.last_update.height);
+        }
+    }
+
+    /// Reload any custom assets that have changed.
+    #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
+    fn reload_assets(&mut self) {
+        for name in self.watcher.changes() {
+            if let Err(e) = self.data.garage.reload_asset(&name) {
+                eprintln!("failed to reload vehicles: {e}");
+            }
+            self.mode.reload_asset(&name);
         }
     }
 
     fn iteration(mut self) -> Self {
+        #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
+        self.reload_assets();
         self.update_controls();
         // update game state
         let (mut i, interp) = self.timer.frame_ticks();
         while i > 0 {
             i -= 1;
-            self.mode = self.mode.tick(&self.data);
-            // clear pressed buttons to avoid triggering stuff if we need to run multiple frames
-            self.data.pressed = Buttons::empty();
-            #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
-            {
-                self.data.scroll_wheel = 0;
-            }
+            self.mode = tick_mode(self.mode, &mut self.data);
+        }
+        // apply any changes to the display settings
+        let display_mode = self.data.settings.borrow().display_mode();
+        if display_mode != self.display_mode {
+            self.display_mode = display_mode;
+            self.last_update = self
+                .update
+                .send_update(RenderUpdate::DisplayMode(display_mode));
+        }
+        let settings = self.data.settings.borrow();
+        if settings.bindings != self.bindings {
+            self.bindings = settings.bindings.clone();
+            self.last_update = self
+                .update
+                .send_update(RenderUpdate::Bindings(self.bindings.clone()));
         }
+        drop(settings);
         // render frame
         let mut graph = RenderGraph::default();
         self.mode.render(
@@ -208,6 +246,84 @@ impl Game {
     }
 }
 
+/// Create the global game data for a new game. The garage is left empty.
+fn init_data(settings: Settings) -> GlobalGameData {
+    let data = GlobalGameData::default();
+    data.settings.replace(settings);
+    data.seats.replace(vec![0]);
+
+    #[cfg(not(target_arch = "wasm32"))]
+    data.should_run.set(true);
+
+    data
+}
+
+/// Apply the deadzone to the controls in each slot, merge them for menus, and
+/// determine which buttons were pressed.
+fn apply_controls(data: &mut GlobalGameData, slots: SlotControls) {
+    let deadzone = data.settings.borrow().deadzone;
+    let mut merged = Controls::default();
+    for (i, mut slot) in slots.into_iter().enumerate() {
+        if let Some(controls) = &mut slot {
+            clean_controls(controls, deadzone);
+            merged.buttons |= controls.buttons;
+            if merged.steering == 0.0 {
+                merged.steering = controls.steering;
+            }
+            merged.throttle = merged.throttle.max(controls.throttle);
+            merged.brake = merged.brake.max(controls.brake);
+        }
+        let held = data.slots[i].map(|controls| controls.buttons);
+        let buttons = slot.map(|controls| controls.buttons);
+        data.slot_pressed[i] = buttons.unwrap_or_default() & !held.unwrap_or_default();
+        data.slots[i] = slot;
+    }
+    clean_controls(&mut merged, deadzone);
+    // determine which buttons were pressed
+    data.pressed = merged.buttons & !data.controls.buttons;
+    data.controls = merged;
+    // give each player the controls from their slot
+    data.players = Default::default();
+    for (player, &slot) in data.players.iter_mut().zip(data.seats.borrow().iter()) {
+        *player = data.slots[slot].unwrap_or_default();
+    }
+}
+
+/// Apply the deadzone and cancel out opposing directions.
+fn clean_controls(controls: &mut Controls, deadzone: f32) {
+    // apply deadzone
+    if controls.steering.abs() < deadzone {
+        controls.steering = 0.0;
+    }
+    if controls.throttle < deadzone {
+        controls.throttle = 0.0;
+    }
+    if controls.brake < deadzone {
+        controls.brake = 0.0;
+    }
+    // cancel out up/down and left/right
+    if controls.buttons.contains(Buttons::UP | Buttons::DOWN) {
+        controls.buttons &= !(Buttons::UP | Buttons::DOWN);
+    }
+    if controls.buttons.contains(Buttons::LEFT | Buttons::RIGHT) {
+        controls.buttons &= !(Buttons::LEFT | Buttons::RIGHT);
+    }
+}
+
+/// Run a single tick of the game mode.
+fn tick_mode(mode: Box<dyn Mode>, data: &mut GlobalGameData) -> Box<dyn Mode> {
+    let mode = mode.tick(data);
+    // clear pressed buttons to avoid triggering stuff if we need to run multiple frames
+    data.pressed = Buttons::empty();
+    data.slot_pressed = [Buttons::empty(); MAX_PLAYERS];
+    data.last_input = None;
+    #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
+    {
+        data.scroll_wheel = 0;
+    }
+    mode
+}
+
 #[cfg(target_arch = "wasm32")]
 use std::{cell::RefCell, rc::Rc};
 
@@ -229,6 +345,7 @@ fn create_closure(keep_alive: Rc<RefCell<Closure<dyn FnMut()>>>, game: Game) ->
 fn generate_update(platform: &mut Impl) -> PlatformUpdate {
     PlatformUpdate {
         controls: platform.poll(),
+        last_input: platform.last_input(),
         width: platform.width(),
         height: platform.height(),
         #[cfg(not(target_arch = "wasm32"))]
@@ -249,11 +366,15 @@ pub fn run_game() {
             sender: render_tx,
             receiver: platform_rx,
         };
-        let mut platform = Impl::init(640, 480);
+        let settings = Settings::load();
+        let display_mode = settings.display_mode();
+        let mut platform = Impl::init(display_mode.width, display_mode.height);
+        platform.set_display_mode(display_mode);
+        platform.set_bindings(&settings.bindings);
 
         // game thread
         let game_thread = std::thread::spawn(move || {
-            let mut game = Game::init(update);
+            let mut game = Game::init(update, settings);
             while game.should_run() {
                 game = game.iteration();
             }
@@ -261,7 +382,7 @@ pub fn run_game() {
             game.end();
         });
         // render thread runs here
-        let font = Font::new().unwrap();
+        let font = Font::new().unwrap_or_else(|e| panic!("failed to load font: {e}"));
         loop {
             // perform update exchange
             let render_update = render_rx.recv().unwrap();
@@ -274,6 +395,8 @@ pub fn run_game() {
                     graph.render(&font, &mut ctx);
                     ctx.finish();
                 }
+                RenderUpdate::DisplayMode(mode) => platform.set_display_mode(mode),
+                RenderUpdate::Bindings(bindings) => platform.set_bindings(&bindings),
             }
         }
         // join game thread when done
@@ -282,16 +405,19 @@ pub fn run_game() {
 
     #[cfg(target_arch = "wasm32")]
     {
-        let font = Font::new().unwrap();
+        let font = Font::new().unwrap_or_else(|e| panic!("failed to load font: {e}"));
+        let settings = Settings::load();
         // implementation of game update for WASM
+        let mut platform = Impl::init(settings.width, settings.height);
+        platform.set_bindings(&settings.bindings);
         let game_update = GameUpdate {
-            platform: RefCell::new(Impl::init(640, 480)),
+            platform: RefCell::new(platform),
             font,
         };
         // keeps the closure alive
         let keep_alive = Rc::new(RefCell::new(Closure::once(|| ())));
         // runs the infinite loop
-        create_closure(keep_alive.clone(), Game::init(game_update))();
+        create_closure(keep_alive.clone(), Game::init(game_update, settings))();
     }
 }
 
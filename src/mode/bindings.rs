@@ -0,0 +1,185 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    platform::{Buttons, RawInput, BUTTON_NAMES, NUM_BUTTONS},
+    render::graph::RenderGraph,
+    settings::Settings,
+};
+
+use super::{options, GlobalGameData, Mode};
+
+/// Lets the player choose which keys and gamepad buttons control the game.
+/// Each part of the keyboard has its own page, followed by a page for
+/// gamepads.
+pub struct BindingsMode {
+    /// The mode that the options menu is shown over, returned to when done.
+    contains: Box<dyn Mode>,
+    /// The page being shown.
+    page: usize,
+    /// The row selected. The last row resets the page to the defaults.
+    selected: usize,
+    /// If true, the next input pressed is bound to the selected button.
+    capturing: bool,
+}
+
+impl BindingsMode {
+    #[must_use]
+    pub fn new(contains: Box<dyn Mode>) -> Self {
+        Self {
+            contains,
+            page: 0,
+            selected: 0,
+            capturing: false,
+        }
+    }
+
+    /// The number of pages.
+    fn pages(data: &GlobalGameData) -> usize {
+        data.settings.borrow().bindings.keys.len() + 1
+    }
+
+    /// Returns true if this page is for gamepads.
+    fn is_gamepad_page(&self, data: &GlobalGameData) -> bool {
+        self.page + 1 == Self::pages(data)
+    }
+
+    /// Bind or unbind a captured input.
+    fn capture(&mut self, data: &GlobalGameData, input: RawInput) {
+        self.capturing = false;
+        let name = match input {
+            RawInput::Key(name) if !self.is_gamepad_page(data) => name,
+            RawInput::Button(name) if self.is_gamepad_page(data) => name,
+            // wrong kind of input for this page
+            _ => return,
+        };
+        let (page, selected) = (self.page, self.selected);
+        options::change(data, |settings| {
+            let bindings = &mut settings.bindings;
+            let buttons = bindings.keys.get_mut(page).unwrap_or(&mut bindings.gamepad);
+            let bound = &buttons[selected];
+            if bound.contains(&name) {
+                // unbind, as long as something is left to press
+                if bound.len() > 1 {
+                    buttons[selected].retain(|n| *n != name);
+                }
+            } else {
+                // an input may only control one button per page, but don't
+                // take it from a button that would be left with nothing
+                let last = buttons
+                    .iter()
+                    .enumerate()
+                    .any(|(i, other)| i != selected && other.len() == 1 && other.contains(&name));
+                if last {
+                    return;
+                }
+                for other in buttons.iter_mut() {
+                    other.retain(|n| *n != name);
+                }
+                buttons[selected].push(name);
+            }
+        });
+    }
+
+    /// Reset the current page to the default bindings.
+    fn reset(&self, data: &GlobalGameData) {
+        let page = self.page;
+        let defaults = Settings::default().bindings;
+        options::change(data, |settings| {
+            let bindings = &mut settings.bindings;
+            if let Some(keys) = bindings.keys.get_mut(page) {
+                *keys = defaults.keys[page].clone();
+            } else {
+                bindings.gamepad = defaults.gamepad;
+            }
+        });
+    }
+}
+
+impl Mode for BindingsMode {
+    fn tick(mut self: Box<Self>, data: &GlobalGameData) -> Box<dyn Mode> {
+        if self.capturing {
+            if let Some(input) = data.last_input.clone() {
+                self.capture(data, input);
+            }
+            return self;
+        }
+
+        let pressed = data.pressed;
+        let pages = Self::pages(data);
+        if pressed.contains(Buttons::BACK) || pressed.contains(Buttons::PAUSE) {
+            return Box::new(options::menu(self.contains));
+        }
+        if pressed.contains(Buttons::UP) && self.selected > 0 {
+            self.selected -= 1;
+        } else if pressed.contains(Buttons::DOWN) && self.selected < NUM_BUTTONS {
+            self.selected += 1;
+        }
+        if pressed.contains(Buttons::LEFT) {
+            self.page = (self.page + pages - 1) % pages;
+        } else if pressed.contains(Buttons::RIGHT) {
+            self.page = (self.page + 1) % pages;
+        }
+        if pressed.contains(Buttons::OK) {
+            if self.selected == NUM_BUTTONS {
+                self.reset(data);
+            } else {
+                self.capturing = true;
+            }
+        }
+        self
+    }
+
+    fn render(
+        &self,
+        _interp: f32,
+        data: &GlobalGameData,
+        graph: &mut RenderGraph,
+        _width: u16,
+        height: u16,
+    ) {
+        let settings = data.settings.borrow();
+        let bindings = &settings.bindings;
+        let (heading, buttons) = match bindings.keys.get(self.page) {
+            Some(keys) => (format!("< KEYBOARD {} >", self.page + 1), keys),
+            None => (String::from("< GAMEPAD >"), &bindings.gamepad),
+        };
+        graph.text(32.0, 16.0, 4.0, String::from("CONTROLS"));
+        graph.text(32.0, 56.0, 3.0, heading);
+        let mut y = 96.0;
+        for (i, (name, bound)) in BUTTON_NAMES.iter().zip(buttons).enumerate() {
+            let value = if self.capturing && i == self.selected {
+                String::from("press an input...")
+            } else if bound.is_empty() {
+                String::from("none")
+            } else {
+                bound.join(", ")
+            };
+            graph.text(48.0, y, 2.0, format!("{name}: {value}"));
+            y += 20.0;
+        }
+        graph.text(48.0, y, 2.0, String::from("defaults"));
+        // cursor
+        let cursor_y = 96.0 + 20.0 * self.selected as f32;
+        graph.text(32.0, cursor_y, 2.0, String::from(">"));
+        graph.text(
+            6.0,
+            f32::from(height) - 22.0,
+            2.0,
+            String::from("OK bind/unbind  LEFT/RIGHT page  BACK done"),
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    catalog::{Catalog, CourseEntry},
+    linalg::{Length, Vector},
+    platform::Buttons,
+    render::graph::{RenderGraph, RenderGraph3d},
+};
+
+use super::{error::ErrorMode, loading::LoadingMode, title::TitleMode, GlobalGameData, Mode};
+
+/// Switches to the next mode once a course has been chosen.
+type OnChosen = Box<dyn Fn(&GlobalGameData, &CourseEntry) -> Box<dyn Mode> + Send>;
+
+/// Lets the player choose a course from the catalog, with a preview of the
+/// selected course.
+pub struct CourseSelectMode {
+    /// The courses to choose from.
+    catalog: Catalog,
+    /// The index of the selected course.
+    selected: usize,
+    /// The angle of the preview camera around the course.
+    angle: f32,
+    /// Called with the chosen course.
+    on_chosen: OnChosen,
+}
+
+impl CourseSelectMode {
+    /// How quickly the preview rotates, in radians per tick.
+    const ROTATE_SPEED: f32 = 0.01;
+
+    /// The number of courses shown in the list at once.
+    const VISIBLE_COURSES: usize = 10;
+
+    /// Find the courses, then let the player choose one.
+    pub fn load<F>(on_chosen: F) -> LoadingMode<Self>
+    where
+        F: Fn(&GlobalGameData, &CourseEntry) -> Box<dyn Mode> + Send + 'static,
+    {
+        LoadingMode::new(move || {
+            Ok(Self {
+                catalog: Catalog::scan(),
+                selected: 0,
+                angle: 0.0,
+                on_chosen: Box::new(on_chosen),
+            })
+        })
+    }
+
+    /// Draw the course spinning in place, framed to fit the view.
+    fn render_preview(&self, course: &CourseEntry, interp: f32, graph: &mut RenderGraph) {
+        let baked = &course.spline.baked;
+        // frame the camera around the course's points
+        let mut center = Vector::default();
+        for baked in baked {
+            center += baked.point;
+        }
+        center /= baked.len() as f32;
+        let radius = baked
+            .iter()
+            .map(|baked| baked.point.dist(center))
+            .fold(0.0, f32::max);
+        let angle = self.angle + Self::ROTATE_SPEED * interp;
+        let eye = center
+            + Vector::new(
+                angle.cos() * radius * 1.5,
+                radius * 0.75,
+                angle.sin() * radius * 1.5,
+            );
+        let mut graph_3d = RenderGraph3d::new(eye, center, Vector::Y_AXIS);
+        course.spline.render(&mut graph_3d, false);
+        graph.graph_3d(graph_3d);
+    }
+}
+
+impl Mode for CourseSelectMode {
+    fn tick(mut self: Box<Self>, data: &GlobalGameData) -> Box<dyn Mode> {
+        if self.catalog.courses.is_empty() {
+            return Box::new(ErrorMode::new(vec![String::from("no courses were found")]));
+        }
+        if data.pressed.contains(Buttons::BACK) {
+            return Box::new(TitleMode::new());
+        }
+        if data.pressed.contains(Buttons::UP) && self.selected > 0 {
+            self.selected -= 1;
+            self.angle = 0.0;
+        } else if data.pressed.contains(Buttons::DOWN)
+            && self.selected + 1 < self.catalog.courses.len()
+        {
+            self.selected += 1;
+            self.angle = 0.0;
+        }
+        if data.pressed.contains(Buttons::OK) {
+            let course = &self.catalog.courses[self.selected];
+            return (self.on_chosen)(data, course);
+        }
+        self.angle += Self::ROTATE_SPEED;
+        self
+    }
+
+    fn render(
+        &self,
+        interp: f32,
+        _data: &GlobalGameData,
+        graph: &mut RenderGraph,
+        width: u16,
+        height: u16,
+    ) {
+        let course = match self.catalog.courses.get(self.selected) {
+            Some(course) => course,
+            // about to return to the title screen
+            None => return,
+        };
+        let width_f32 = f32::from(width);
+        let height_f32 = f32::from(height);
+        graph.text(32.0, 16.0, 4.0, String::from("SELECT COURSE"));
+        // list the courses, scrolling to keep the selected one visible
+        let first = self.selected.saturating_sub(Self::VISIBLE_COURSES - 1);
+        let mut y = 72.0;
+        for (i, entry) in self
+            .catalog
+            .courses
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(Self::VISIBLE_COURSES)
+        {
+            if i == self.selected {
+                graph.text(16.0, y, 3.0, String::from(">"));
+            }
+            graph.text(40.0, y, 3.0, entry.name.clone());
+            y += 28.0;
+        }
+        // preview the selected course on the right half
+        let preview_x = (width_f32 * 0.5).floor();
+        let preview_width = (width / 2).saturating_sub(16);
+        let preview_height = height.saturating_sub(180);
+        let mut preview = RenderGraph::default();
+        self.render_preview(course, interp, &mut preview);
+        graph.viewport(preview_x, 64.0, preview_width, preview_height, preview);
+        let info_y = 64.0 + f32::from(preview_height) + 8.0;
+        graph.text(preview_x, info_y, 3.0, course.name.clone());
+        if let Some(author) = &course.meta.author {
+            graph.text(preview_x, info_y + 28.0, 2.0, format!("by {author}"));
+        }
+        let mut details = format!("length: {:.0}", course.spline.length);
+        if !course.spline.is_closed() {
+            details += "  point to point";
+        } else if let Some(laps) = course.meta.laps {
+            details += &format!("  laps: {laps}");
+        }
+        graph.text(preview_x, info_y + 48.0, 2.0, details);
+        graph.text(
+            6.0,
+            height_f32 - 22.0,
+            2.0,
+            String::from("OK choose  BACK return"),
+        );
+    }
+}
@@ -14,52 +14,369 @@
 //! You should have received a copy of the GNU General Public License
 //! along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::{
+    f32::consts::{PI, TAU},
+    io,
+    sync::Arc,
+};
+
 use crate::{
-    assets::Asset,
+    assets::{Asset, AssetError},
+    course::{Course, CoursePoint},
     linalg::{Length, Mtx, Quat, Vector},
-    platform::Buttons,
-    render::graph::{RenderGraph, RenderGraph3d},
+    platform::{Buttons, RawInput},
+    render::{
+        camera::Camera,
+        graph::{RenderGraph, RenderGraph3d},
+    },
     spline::Spline,
+    timing::TICK_DELTA,
 };
 
-use super::{loading::LoadingMode, title::TitleMode, GlobalGameData, Mode};
+use super::{
+    loading::LoadingMode, menu::MenuOption, pause::PauseMode, title::TitleMode, GlobalGameData,
+    Mode,
+};
 
 pub struct EditorMode {
+    /// The name of the course asset being edited.
+    file: String,
+    /// The course being edited.
+    course: Course,
+    /// The spline built from the course, rebuilt whenever it changes.
     spline: Spline,
     focus_pos: Vector,
     rotation: Quat,
     last_mouse_x: i32,
     last_mouse_y: i32,
+    /// If true, the left mouse button was held last tick.
+    last_mouse_left: bool,
+    /// If true, the right mouse button was held last tick.
+    last_mouse_right: bool,
+    /// The indices of the selected control points, with the most recently
+    /// selected last.
+    selected: Vec<usize>,
+    /// If true, the most recently selected point follows the mouse.
+    dragging: bool,
+    /// If true, the course has been edited since it was loaded or saved.
+    modified: bool,
 }
 
 impl EditorMode {
-    pub fn load() -> LoadingMode<EditorMode> {
-        LoadingMode::new(|| {
-            let spline = Spline::load(&mut Asset::load("course_test1.bin").unwrap()).unwrap();
-            Self {
+    /// How close in pixels the mouse must be to a point to select it.
+    const PICK_RADIUS: f32 = 12.0;
+    /// The size of the markers drawn on control points.
+    const MARKER_SIZE: f32 = 0.25;
+    /// How fast the tilt keys turn points, in radians per second.
+    const TILT_SPEED: f32 = PI / 2.0;
+    /// How far one step of the scroll wheel turns points, in radians.
+    const TILT_STEP: f32 = PI / 32.0;
+
+    pub fn load(file: String) -> LoadingMode<EditorMode> {
+        LoadingMode::new(move || {
+            let course = load_course_data(&file)?;
+            let spline = Spline::new(&course);
+            Ok(Self {
+                file,
+                course,
                 spline,
                 focus_pos: Vector::Z_AXIS,
                 rotation: Quat::IDENT,
                 last_mouse_x: 0,
                 last_mouse_y: 0,
-            }
+                last_mouse_left: false,
+                last_mouse_right: false,
+                selected: vec![],
+                dragging: false,
+                modified: false,
+            })
         })
     }
+
+    /// Get the position, target and up vector of the camera.
+    fn view(&self) -> (Vector, Vector, Vector) {
+        let rotation_mtx = Mtx::from(self.rotation);
+        let camera_pos = (rotation_mtx * (Vector::Z_AXIS * -10.0)) + self.focus_pos;
+        let camera_up = rotation_mtx * Vector::Y_AXIS;
+        (camera_pos, self.focus_pos, camera_up)
+    }
+
+    fn camera(&self) -> Camera {
+        let (eye, at, up) = self.view();
+        Camera::new(eye, at, up)
+    }
+
+    /// Find the control point closest to a position on the screen, if any are
+    /// close enough to select.
+    fn pick(&self, data: &GlobalGameData, x: f32, y: f32) -> Option<usize> {
+        let camera = self.camera();
+        let (width, height) = data.screen_size;
+        let (width, height) = (f32::from(width), f32::from(height));
+        let mut closest = None;
+        let mut closest_dist_sq = Self::PICK_RADIUS * Self::PICK_RADIUS;
+        for (index, point) in self.course.points.iter().enumerate() {
+            if let Some((px, py)) = camera.project(point.position, width, height) {
+                let dist_sq = (px - x) * (px - x) + (py - y) * (py - y);
+                if dist_sq <= closest_dist_sq {
+                    closest = Some(index);
+                    closest_dist_sq = dist_sq;
+                }
+            }
+        }
+        closest
+    }
+
+    /// Find the control point under the mouse, if any.
+    fn pick_mouse(&self, data: &GlobalGameData) -> Option<usize> {
+        self.pick(data, data.mouse_state.x as f32, data.mouse_state.y as f32)
+    }
+
+    /// Rebuild the spline after the points change, or undo the change if the
+    /// spline can't be built through them. Returns true if the change was kept.
+    fn update_points(&mut self, old_points: Vec<CoursePoint>) -> bool {
+        if self.course.has_overlapping_points() {
+            self.course.points = old_points;
+            false
+        } else {
+            self.spline = Spline::new(&self.course);
+            self.modified = true;
+            true
+        }
+    }
+
+    /// Move the selected point with the mouse, in the plane facing the camera.
+    fn drag(&mut self, data: &GlobalGameData) {
+        let index = match self.selected.last() {
+            Some(&index) => index,
+            None => return,
+        };
+        let camera = self.camera();
+        let (width, height) = data.screen_size;
+        let (width, height) = (f32::from(width), f32::from(height));
+        let old_position = self.course.points[index].position;
+        let depth = camera.transform(old_position).z;
+        if depth <= 0.0 {
+            // can't drag points behind the camera
+            return;
+        }
+        let from = camera.unproject(
+            self.last_mouse_x as f32,
+            self.last_mouse_y as f32,
+            depth,
+            width,
+            height,
+        );
+        let to = camera.unproject(
+            data.mouse_state.x as f32,
+            data.mouse_state.y as f32,
+            depth,
+            width,
+            height,
+        );
+        if from.dist_sq(to) == 0.0 {
+            return;
+        }
+        let old_points = self.course.points.clone();
+        self.course.points[index].position = old_position + (to - from);
+        self.update_points(old_points);
+    }
+
+    /// Turn the selected points by an angle.
+    fn tilt(&mut self, angle: f32) {
+        if self.selected.is_empty() {
+            return;
+        }
+        let old_points = self.course.points.clone();
+        for &index in &self.selected {
+            let point = &mut self.course.points[index];
+            point.tilt = (point.tilt + angle).rem_euclid(TAU);
+        }
+        // rebuilding the spline unwraps the tilts again
+        self.update_points(old_points);
+    }
+
+    /// Insert a point between the two selected points, if they're next to each
+    /// other, and select it.
+    fn insert(&mut self) {
+        let (a, b) = match self.selected[..] {
+            [a, b] => (a.min(b), a.max(b)),
+            _ => return,
+        };
+        let len = self.course.points.len();
+        let index = if b - a == 1 {
+            b
+        } else if self.course.closed && a == 0 && b == len - 1 {
+            // the last point connects back to the first
+            len
+        } else {
+            return;
+        };
+        let prev = self.course.points[index - 1];
+        let next = self.course.points[index % len];
+        // turn the shorter way between the two tilts
+        let delta = (next.tilt - prev.tilt + PI).rem_euclid(TAU) - PI;
+        let point = CoursePoint {
+            position: (prev.position + next.position) * 0.5,
+            tilt: (prev.tilt + delta * 0.5).rem_euclid(TAU),
+            width: (prev.width + next.width) * 0.5,
+        };
+        let old_points = self.course.points.clone();
+        self.course.points.insert(index, point);
+        if self.update_points(old_points) {
+            self.selected = vec![index];
+        }
+    }
+
+    /// Delete the selected points, as long as enough remain for a course.
+    fn delete(&mut self) {
+        if self.selected.is_empty()
+            || self.course.points.len() - self.selected.len() < Course::MIN_POINTS
+        {
+            return;
+        }
+        let old_points = self.course.points.clone();
+        // remove from the back so that the other indices stay the same
+        let mut indices = self.selected.clone();
+        indices.sort_unstable();
+        for &index in indices.iter().rev() {
+            self.course.points.remove(index);
+        }
+        if self.update_points(old_points) {
+            self.selected.clear();
+            self.dragging = false;
+        }
+    }
+
+    /// Save the course over the custom copy of its file.
+    fn save(&mut self) {
+        match save_course(&self.file, &self.course) {
+            Ok(()) => self.modified = false,
+            Err(e) => eprintln!("failed to save course: {e}"),
+        }
+    }
+
+    /// Ask what to do with the unsaved edits before leaving the editor.
+    fn confirm_quit(self: Box<Self>) -> Box<dyn Mode> {
+        // the course can't change while the menu is open
+        let file = self.file.clone();
+        let course = self.course.clone();
+        Box::new(
+            PauseMode::new(
+                self,
+                vec![
+                    MenuOption::previous(String::from("keep editing")),
+                    MenuOption::wrap(String::from("save and quit"), move |_, editor| {
+                        match save_course(&file, &course) {
+                            Ok(()) => Box::new(TitleMode::new()),
+                            Err(e) => {
+                                eprintln!("failed to save course: {e}");
+                                editor
+                            }
+                        }
+                    }),
+                    MenuOption::switch(String::from("quit without saving"), |_| {
+                        Box::new(TitleMode::new())
+                    }),
+                ],
+            )
+            .with_title(String::from("UNSAVED"))
+            .with_menu_scale(2.0),
+        )
+    }
+
+    /// Get the lines marking the control points.
+    fn markers(&self) -> Vec<(Vector, Vector)> {
+        let mut lines = vec![];
+        for (index, point) in self.course.points.iter().enumerate() {
+            // make the selected point stand out
+            let size = if self.selected.contains(&index) {
+                Self::MARKER_SIZE * 2.0
+            } else {
+                Self::MARKER_SIZE
+            };
+            for axis in [Vector::X_AXIS, Vector::Y_AXIS, Vector::Z_AXIS] {
+                let axis = axis * size;
+                lines.push((point.position - axis, point.position + axis));
+            }
+        }
+        lines
+    }
+}
+
+/// Load a course for editing by file name.
+fn load_course_data(file: &str) -> Result<Course, AssetError> {
+    Course::load(&mut Asset::load(file)?)
+}
+
+/// Save a course to the content directory, where it replaces the course of
+/// the same name.
+fn save_course(file: &str, course: &Course) -> io::Result<()> {
+    let path = Asset::custom_path(file);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    course.save(&path)
 }
 
 impl Mode for EditorMode {
     fn tick(mut self: Box<Self>, data: &GlobalGameData) -> Box<dyn Mode> {
         // if back button pressed, quit editor
         if data.pressed.contains(Buttons::BACK) {
-            return Box::new(TitleMode);
+            if self.modified {
+                return self.confirm_quit();
+            }
+            return Box::new(TitleMode::new());
+        }
+        if data.pressed.contains(Buttons::PAUSE) {
+            self.save();
+        }
+
+        let left = data.mouse_state.left;
+        if left && !self.last_mouse_left {
+            // clicking a point selects it, clicking elsewhere deselects
+            self.selected = self.pick_mouse(data).into_iter().collect();
+            self.dragging = !self.selected.is_empty();
+        } else if !left {
+            self.dragging = false;
+        }
+        let right = data.mouse_state.right;
+        if right && !self.last_mouse_right && !self.dragging {
+            // right clicking a point adds it to or removes it from the selection
+            if let Some(index) = self.pick_mouse(data) {
+                match self.selected.iter().position(|&i| i == index) {
+                    Some(position) => {
+                        self.selected.remove(position);
+                    }
+                    None => self.selected.push(index),
+                }
+            }
         }
 
-        // if mouse down, pan camera
-        if data.mouse_state.left() {
+        // edit the points
+        let key = match &data.last_input {
+            Some(RawInput::Key(key)) => Some(key.as_str()),
+            _ => None,
+        };
+        if data.pressed.contains(Buttons::OK) || key == Some("Insert") {
+            self.insert();
+        } else if key == Some("Delete") || key == Some("Backspace") {
+            self.delete();
+        }
+        if data.controls.buttons.contains(Buttons::LEFT) {
+            self.tilt(-Self::TILT_SPEED * TICK_DELTA);
+        } else if data.controls.buttons.contains(Buttons::RIGHT) {
+            self.tilt(Self::TILT_SPEED * TICK_DELTA);
+        }
+
+        if left && self.dragging {
+            // if dragging a point, move it
+            self.drag(data);
+        } else if left {
+            // otherwise, if mouse down, pan camera
             let rotation_mtx = Mtx::from(self.rotation);
             let local_x = rotation_mtx * Vector::X_AXIS;
-            let dx = data.mouse_state.x() - self.last_mouse_x;
-            let dy = data.mouse_state.y() - self.last_mouse_y;
+            let dx = data.mouse_state.x - self.last_mouse_x;
+            let dy = data.mouse_state.y - self.last_mouse_y;
             self.rotation *= Quat::axis_angle(&Vector::Y_AXIS, (dx as f32) * 0.015);
             self.rotation *= Quat::axis_angle(&local_x, (dy as f32) * 0.015);
 
@@ -84,7 +401,10 @@ impl Mode for EditorMode {
             self.rotation = self.rotation.normalized();
         }
 
-        if data.scroll_wheel != 0 {
+        if data.scroll_wheel != 0 && self.dragging {
+            // scrolling while holding a point tilts it
+            self.tilt(data.scroll_wheel as f32 * Self::TILT_STEP);
+        } else if data.scroll_wheel != 0 {
             let rotation_mtx = Mtx::from(self.rotation);
             let our_forward = rotation_mtx * Vector::Z_AXIS;
 
@@ -92,8 +412,10 @@ impl Mode for EditorMode {
         }
 
         // update mouse position
-        self.last_mouse_x = data.mouse_state.x();
-        self.last_mouse_y = data.mouse_state.y();
+        self.last_mouse_x = data.mouse_state.x;
+        self.last_mouse_y = data.mouse_state.y;
+        self.last_mouse_left = left;
+        self.last_mouse_right = right;
 
         self
     }
@@ -104,14 +426,41 @@ impl Mode for EditorMode {
         _data: &GlobalGameData,
         graph: &mut RenderGraph,
         _width: u16,
-        _height: u16,
+        height: u16,
     ) {
         // TODO interpolation
-        let rotation_mtx = Mtx::from(self.rotation);
-        let camera_pos = (rotation_mtx * (Vector::Z_AXIS * -10.0)) + self.focus_pos;
-        let camera_up = rotation_mtx * Vector::Y_AXIS;
-        let mut graph_3d = RenderGraph3d::new(camera_pos, self.focus_pos, camera_up);
+        let (camera_pos, focus_pos, camera_up) = self.view();
+        let mut graph_3d = RenderGraph3d::new(camera_pos, focus_pos, camera_up);
         self.spline.render(&mut graph_3d, false);
+        graph_3d.lines(Arc::new(self.markers()));
         graph.graph_3d(graph_3d);
+        let status = if self.modified {
+            "PAUSE save  BACK quit  (unsaved)"
+        } else {
+            "PAUSE save  BACK quit"
+        };
+        graph.text(6.0, f32::from(height) - 22.0, 2.0, String::from(status));
+    }
+
+    fn reload_asset(&mut self, name: &str) {
+        if name != self.file {
+            return;
+        }
+        if self.modified {
+            // don't throw away the edits
+            eprintln!("not reloading {name}: the course has unsaved edits");
+            return;
+        }
+        match load_course_data(name) {
+            Ok(course) => {
+                self.spline = Spline::new(&course);
+                self.course = course;
+                // saving reloads the course, so keep the selection if it can
+                let len = self.course.points.len();
+                self.selected.retain(|&index| index < len);
+                self.dragging = false;
+            }
+            Err(e) => eprintln!("failed to reload course: {e}"),
+        }
     }
 }
@@ -0,0 +1,77 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{assets::AssetError, platform::Buttons, render::graph::RenderGraph};
+
+use super::{title::TitleMode, GlobalGameData, Mode};
+
+/// Shows why something went wrong, then returns to the title screen.
+pub struct ErrorMode {
+    /// The lines of the message.
+    lines: Vec<String>,
+}
+
+impl ErrorMode {
+    #[must_use]
+    pub fn new(lines: Vec<String>) -> Self {
+        for line in &lines {
+            eprintln!("{line}");
+        }
+        Self { lines }
+    }
+
+    /// Explain why an asset failed to load.
+    #[must_use]
+    pub fn asset(error: &AssetError) -> Self {
+        Self::new(vec![
+            format!("failed to load {}", error.file),
+            format!("at byte {}:", error.offset),
+            error.kind.to_string(),
+        ])
+    }
+}
+
+impl Mode for ErrorMode {
+    fn tick(self: Box<Self>, data: &GlobalGameData) -> Box<dyn Mode> {
+        if data.pressed.intersects(Buttons::OK | Buttons::BACK) {
+            return Box::new(TitleMode::new());
+        }
+        self
+    }
+
+    fn render(
+        &self,
+        _interp: f32,
+        _data: &GlobalGameData,
+        graph: &mut RenderGraph,
+        width: u16,
+        height: u16,
+    ) {
+        let center = f32::from(width) * 0.5;
+        graph.text_centered(center, 32.0, 6.0, String::from("ERROR"));
+        let mut y = 120.0;
+        for line in &self.lines {
+            graph.text_centered(center, y, 2.0, line.clone());
+            y += 20.0;
+        }
+        graph.text(
+            6.0,
+            f32::from(height) - 22.0,
+            2.0,
+            String::from("OK return to title"),
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    platform::{Buttons, MAX_PLAYERS},
+    render::graph::RenderGraph,
+};
+
+use super::{
+    course_select::CourseSelectMode,
+    race::{RaceMode, RaceSetup},
+    title::TitleMode,
+    vehicle_select::VehicleSelectMode,
+    GlobalGameData, Mode,
+};
+
+/// Lets each player join a race by pressing OK on their controls.
+pub struct JoinMode {
+    /// The slots that have joined, in the order they joined.
+    joined: Vec<usize>,
+}
+
+impl JoinMode {
+    #[must_use]
+    pub fn new() -> Self {
+        Self { joined: vec![] }
+    }
+
+    /// Choose a course, then start the race with the joined players.
+    fn start(&self, data: &GlobalGameData) -> Box<dyn Mode> {
+        data.seats.replace(self.joined.clone());
+        let setup = RaceSetup {
+            players: self.joined.len() as u8,
+            opponents: data.settings.borrow().opponents,
+            ..RaceSetup::default()
+        };
+        Box::new(CourseSelectMode::load(move |_, course| {
+            let setup = RaceSetup {
+                course: course.file.clone(),
+                laps: course.meta.laps.unwrap_or(setup.laps),
+                ..setup.clone()
+            };
+            Box::new(VehicleSelectMode::new(move |data, model| {
+                let setup = RaceSetup {
+                    model: String::from(model),
+                    ..setup.clone()
+                };
+                Box::new(RaceMode::initializing(&data.garage, setup))
+            }))
+        }))
+    }
+}
+
+impl Mode for JoinMode {
+    fn tick(mut self: Box<Self>, data: &GlobalGameData) -> Box<dyn Mode> {
+        for slot in 0..MAX_PLAYERS {
+            let pressed = data.slot_pressed[slot];
+            let joined = self.joined.contains(&slot);
+            if pressed.contains(Buttons::OK) {
+                if joined {
+                    // a joined player is ready to go
+                    return self.start(data);
+                }
+                self.joined.push(slot);
+            } else if pressed.contains(Buttons::BACK) {
+                if !joined {
+                    // nobody wants to race
+                    return Box::new(TitleMode::new());
+                }
+                self.joined.retain(|&s| s != slot);
+            }
+        }
+        self
+    }
+
+    fn render(
+        &self,
+        _interp: f32,
+        data: &GlobalGameData,
+        graph: &mut RenderGraph,
+        width: u16,
+        height: u16,
+    ) {
+        let center = f32::from(width) * 0.5;
+        graph.text_centered(center, 32.0, 4.0, String::from("PRESS OK TO JOIN"));
+        let mut y = 96.0;
+        for slot in 0..MAX_PLAYERS {
+            let status = if let Some(player) = self.joined.iter().position(|&s| s == slot) {
+                format!("P{} READY", player + 1)
+            } else if data.slots[slot].is_some() {
+                String::from("PRESS OK")
+            } else {
+                String::from("NO CONTROLLER")
+            };
+            graph.text(32.0, y, 3.0, format!("SLOT {}  {status}", slot + 1));
+            y += 24.0;
+        }
+        graph.text(
+            6.0,
+            f32::from(height) - 22.0,
+            2.0,
+            String::from("OK join/start  BACK leave"),
+        );
+    }
+}
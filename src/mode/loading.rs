@@ -17,18 +17,18 @@
 #[cfg(not(target_arch = "wasm32"))]
 use std::thread::JoinHandle;
 
-use crate::render::graph::RenderGraph;
+use crate::{assets::AssetError, render::graph::RenderGraph};
 
-use super::{GlobalGameData, Mode};
+use super::{error::ErrorMode, GlobalGameData, Mode};
 
 /// A mode that allows potentially blocking or long-running processes to run
-/// without blocking the game thread.
+/// without blocking the game thread. If loading fails, the error is shown.
 /// TODO WASM support - for WASM we just let the operation block for now
 pub struct LoadingMode<T> {
     #[cfg(not(target_arch = "wasm32"))]
-    thread: JoinHandle<T>,
+    thread: JoinHandle<Result<T, AssetError>>,
     #[cfg(target_arch = "wasm32")]
-    function: Box<dyn FnOnce() -> T + Send + 'static>,
+    function: Box<dyn FnOnce() -> Result<T, AssetError> + Send + 'static>,
 }
 
 impl<T> LoadingMode<T>
@@ -37,7 +37,7 @@ where
 {
     pub fn new<F>(f: F) -> Self
     where
-        F: FnOnce() -> T + Send + 'static,
+        F: FnOnce() -> Result<T, AssetError> + Send + 'static,
     {
         #[cfg(not(target_arch = "wasm32"))]
         {
@@ -54,6 +54,17 @@ where
     }
 }
 
+/// Switch to the loaded mode, or show why it failed to load.
+fn loaded<T>(result: Result<T, AssetError>) -> Box<dyn Mode>
+where
+    T: Mode + 'static,
+{
+    match result {
+        Ok(mode) => Box::new(mode),
+        Err(e) => Box::new(ErrorMode::asset(&e)),
+    }
+}
+
 fn render_mono(graph: &mut RenderGraph, height: u16) {
     graph.text(
         16.0,
@@ -71,14 +82,17 @@ where
         #[cfg(not(target_arch = "wasm32"))]
         if self.thread.is_finished() {
             // thread is done loading, switch to the newly loaded mode
-            Box::new(self.thread.join().unwrap())
+            match self.thread.join() {
+                Ok(result) => loaded(result),
+                Err(_) => Box::new(ErrorMode::new(vec![String::from("loading failed")])),
+            }
         } else {
             // still loading
             self
         }
         #[cfg(target_arch = "wasm32")]
         // blocks until completion on wasm
-        Box::new((self.function)())
+        loaded((self.function)())
     }
 
     fn render(
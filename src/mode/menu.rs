@@ -0,0 +1,171 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    platform::Buttons,
+    render::{graph::RenderGraph, Font},
+};
+
+use super::{GlobalGameData, Mode};
+
+/// Produces a new mode around the previous mode.
+type WrapFn = Box<dyn Fn(&GlobalGameData, Box<dyn Mode>) -> Box<dyn Mode>>;
+
+/// An action that a menu option can take.
+pub enum MenuAction {
+    /// Action switches to previous mode.
+    Previous,
+    /// Action loads new mode.
+    Switch(Box<dyn Fn(&GlobalGameData) -> Box<dyn Mode>>),
+    /// Action loads new mode, which is given the previous mode to return to.
+    Wrap(WrapFn),
+    /// Action uses global data but does not switch mode.
+    Data(Box<dyn Fn(&GlobalGameData)>),
+}
+
+/// Produces the value shown next to a menu option.
+type MenuValue = Box<dyn Fn(&GlobalGameData) -> String>;
+
+/// An option in a menu.
+pub struct MenuOption {
+    /// The name of the option.
+    name: String,
+    /// Shows the current value of a setting next to the name, if any.
+    value: Option<MenuValue>,
+    /// The action to take when this option is selected.
+    /// This function takes in the previous mode and returns a new mode to switch to.
+    action: MenuAction,
+}
+
+impl MenuOption {
+    pub fn previous(name: String) -> Self {
+        Self {
+            name,
+            value: None,
+            action: MenuAction::Previous,
+        }
+    }
+
+    pub fn switch<F>(name: String, f: F) -> Self
+    where
+        F: Fn(&GlobalGameData) -> Box<dyn Mode> + 'static,
+    {
+        Self {
+            name,
+            value: None,
+            action: MenuAction::Switch(Box::new(f)),
+        }
+    }
+
+    pub fn wrap<F>(name: String, f: F) -> Self
+    where
+        F: Fn(&GlobalGameData, Box<dyn Mode>) -> Box<dyn Mode> + 'static,
+    {
+        Self {
+            name,
+            value: None,
+            action: MenuAction::Wrap(Box::new(f)),
+        }
+    }
+
+    pub fn data<F>(name: String, f: F) -> Self
+    where
+        F: Fn(&GlobalGameData) + 'static,
+    {
+        Self {
+            name,
+            value: None,
+            action: MenuAction::Data(Box::new(f)),
+        }
+    }
+
+    /// Show a value next to the name of the option.
+    #[must_use]
+    pub fn with_value<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&GlobalGameData) -> String + 'static,
+    {
+        self.value = Some(Box::new(f));
+        self
+    }
+
+    /// The text to show for this option.
+    fn label(&self, data: &GlobalGameData) -> String {
+        match &self.value {
+            Some(value) => format!("{}: {}", self.name, value(data)),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// A vertical list of options with a cursor.
+pub struct Menu {
+    options: Vec<MenuOption>,
+    selected: usize,
+    /// The scale of the option text.
+    scale: f32,
+}
+
+impl Menu {
+    #[must_use]
+    pub fn new(options: Vec<MenuOption>) -> Self {
+        Self {
+            options,
+            selected: 0,
+            scale: Self::OPTION_SCALE,
+        }
+    }
+
+    const OPTION_SCALE: f32 = 3.0;
+
+    /// Change the scale of the option text, to fit longer options.
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale;
+    }
+
+    /// Move the cursor according to the pressed buttons. If an option was
+    /// chosen, returns its action.
+    pub fn tick(&mut self, data: &GlobalGameData) -> Option<&MenuAction> {
+        if data.pressed.contains(Buttons::UP) && self.selected > 0 {
+            // previous option
+            self.selected -= 1;
+        } else if data.pressed.contains(Buttons::DOWN) && self.selected + 1 < self.options.len() {
+            // next option
+            self.selected += 1;
+        }
+
+        if data.pressed.contains(Buttons::OK) {
+            // select this option
+            Some(&self.options[self.selected].action)
+        } else {
+            None
+        }
+    }
+
+    /// Draw the options starting at the given position.
+    pub fn render(&self, data: &GlobalGameData, graph: &mut RenderGraph, x: f32, mut y: f32) {
+        for (i, option) in self.options.iter().enumerate() {
+            let mut x = x;
+            // show cursor where we're selecting
+            if i == self.selected {
+                graph.text(x, y, self.scale, String::from(">"));
+                x += Font::GLYPH_SPACING * 2.0 * self.scale;
+            }
+            graph.text(x, y, self.scale, option.label(data));
+            y += self.scale * 8.0;
+        }
+    }
+}
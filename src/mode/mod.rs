@@ -14,55 +14,94 @@
 //! You should have received a copy of the GNU General Public License
 //! along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+#[cfg(not(target_os = "horizon"))]
+pub mod bindings;
 #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
 pub mod editor;
 
+pub mod course_select;
+pub mod error;
+pub mod join;
 pub mod loading;
+pub mod menu;
+pub mod options;
 pub mod pause;
 pub mod race;
+pub mod replay;
+pub mod results;
 pub mod title;
+pub mod vehicle_select;
 
+#[cfg(not(target_arch = "wasm32"))]
 use std::cell::Cell;
+use std::cell::RefCell;
 
+#[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
+use crate::platform::MouseState;
 use crate::{
-    platform::{Buttons, Controls},
+    platform::{Buttons, Controls, PlayerControls, RawInput, SlotControls, MAX_PLAYERS},
     render::graph::RenderGraph,
+    settings::Settings,
     vehicle::garage::Garage,
 };
 
+use self::race::RaceMode;
+
 pub struct GlobalGameData {
-    /// The last state of the controls.
+    /// The last state of the controls, merged from every player.
     pub controls: Controls,
+    /// The last state of each player's controls.
+    pub players: PlayerControls,
     /// The buttons that have been pressed this frame.
     pub pressed: Buttons,
+    /// The last state of the controls in each slot, or None if nothing is
+    /// connected to the slot.
+    pub slots: SlotControls,
+    /// The buttons that have been pressed this frame in each slot.
+    pub slot_pressed: [Buttons; MAX_PLAYERS],
+    /// The key or gamepad button that was just pressed, if any.
+    pub last_input: Option<RawInput>,
+    /// The slot that each player's controls come from.
+    pub seats: RefCell<Vec<usize>>,
     /// The vehicle models.
     pub garage: Garage,
-    /// If true, enable walls. Stored in a Cell so it may be modified easily.
-    pub walls: Cell<bool>,
+    /// The player's settings. Stored in a RefCell so they may be modified easily.
+    pub settings: RefCell<Settings>,
     /// If false, the game stops running. Not present on web target.
     #[cfg(not(target_arch = "wasm32"))]
     pub should_run: Cell<bool>,
     /// The mouse state, used for the editor.
     #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
-    pub mouse_state: sdl2::mouse::MouseState,
+    pub mouse_state: MouseState,
     /// The mouse scroll wheel this frame.
     #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
     pub scroll_wheel: i32,
+    /// The size of the screen, used by the editor to find what the mouse is
+    /// pointing at.
+    #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
+    pub screen_size: (u16, u16),
 }
 
 impl Default for GlobalGameData {
     fn default() -> Self {
         Self {
             controls: Controls::default(),
+            players: PlayerControls::default(),
             pressed: Buttons::default(),
+            slots: SlotControls::default(),
+            slot_pressed: [Buttons::default(); MAX_PLAYERS],
+            last_input: None,
+            seats: RefCell::<Vec<usize>>::default(),
             garage: Garage::default(),
-            walls: Cell::<bool>::default(),
+            settings: RefCell::<Settings>::default(),
             #[cfg(not(target_arch = "wasm32"))]
             should_run: Cell::<bool>::default(),
             #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
-            mouse_state: sdl2::mouse::MouseState::from_sdl_state(0),
+            mouse_state: MouseState::default(),
             #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
             scroll_wheel: 0,
+            #[cfg(not(any(target_arch = "wasm32", target_os = "horizon")))]
+            screen_size: (0, 0),
         }
     }
 }
@@ -82,4 +121,17 @@ pub trait Mode {
         width: u16,
         height: u16,
     );
+
+    /// Returns the race shown by this mode, if any. This allows the race state
+    /// to be inspected, such as when running headless.
+    fn race(&self) -> Option<&RaceMode> {
+        None
+    }
+
+    /// Called when an asset has changed on disk, so that anything loaded from
+    /// it can be reloaded.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    fn reload_asset(&mut self, _name: &str) {
+        // default implementation if nothing needs to be reloaded
+    }
 }
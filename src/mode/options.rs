@@ -0,0 +1,118 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::settings::Settings;
+
+use super::{menu::MenuOption, pause::PauseMode, GlobalGameData, Mode};
+
+#[cfg(not(target_os = "horizon"))]
+use super::bindings::BindingsMode;
+
+/// The deadzones that can be cycled through.
+const DEADZONES: [f32; 6] = [0.0, 0.03, 0.05, 0.1, 0.15, 0.2];
+
+/// The window sizes that can be cycled through.
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+const WINDOW_SIZES: [(u16, u16); 5] = [
+    (640, 480),
+    (800, 600),
+    (1024, 768),
+    (1280, 720),
+    (1920, 1080),
+];
+
+/// Change the settings and save them.
+pub fn change<F>(data: &GlobalGameData, f: F)
+where
+    F: FnOnce(&mut Settings),
+{
+    let mut settings = data.settings.borrow_mut();
+    f(&mut settings);
+    settings.save();
+}
+
+fn on_off(value: bool) -> String {
+    String::from(if value { "on" } else { "off" })
+}
+
+/// An option that toggles walls.
+#[must_use]
+pub fn walls() -> MenuOption {
+    MenuOption::data(String::from("walls"), |data| {
+        change(data, |settings| settings.walls = !settings.walls);
+    })
+    .with_value(|data| on_off(data.settings.borrow().walls))
+}
+
+/// The options menu, shown over the given mode. Changes are saved immediately.
+#[must_use]
+pub fn menu(contains: Box<dyn Mode>) -> PauseMode {
+    let mut options = vec![
+        walls(),
+        MenuOption::data(String::from("deadzone"), |data| {
+            change(data, |settings| {
+                // pick the next largest deadzone, wrapping around
+                settings.deadzone = DEADZONES
+                    .into_iter()
+                    .find(|&deadzone| deadzone > settings.deadzone)
+                    .unwrap_or(DEADZONES[0]);
+            });
+        })
+        .with_value(|data| format!("{:.0}%", data.settings.borrow().deadzone * 100.0)),
+        MenuOption::data(String::from("opponents"), |data| {
+            change(data, |settings| {
+                settings.opponents = (settings.opponents + 1) % (Settings::MAX_OPPONENTS + 1);
+            });
+        })
+        .with_value(|data| data.settings.borrow().opponents.to_string()),
+    ];
+
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    {
+        options.push(
+            MenuOption::data(String::from("window"), |data| {
+                change(data, |settings| {
+                    // pick the next size, wrapping around
+                    let current = (settings.width, settings.height);
+                    let index = WINDOW_SIZES.iter().position(|&size| size == current);
+                    let next = index.map_or(0, |i| (i + 1) % WINDOW_SIZES.len());
+                    (settings.width, settings.height) = WINDOW_SIZES[next];
+                });
+            })
+            .with_value(|data| {
+                let settings = data.settings.borrow();
+                format!("{}x{}", settings.width, settings.height)
+            }),
+        );
+        options.push(
+            MenuOption::data(String::from("fullscreen"), |data| {
+                change(data, |settings| settings.fullscreen = !settings.fullscreen);
+            })
+            .with_value(|data| on_off(data.settings.borrow().fullscreen)),
+        );
+    }
+
+    #[cfg(not(target_os = "horizon"))]
+    options.push(MenuOption::wrap(String::from("controls"), |_, contains| {
+        Box::new(BindingsMode::new(contains))
+    }));
+
+    options.push(MenuOption::previous(String::from("back")));
+
+    PauseMode::new(contains, options)
+        .with_title(String::from("OPTIONS"))
+        .with_menu_scale(2.0)
+}
@@ -14,80 +14,46 @@
 //! You should have received a copy of the GNU General Public License
 //! along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::{
-    platform::Buttons,
-    render::{graph::RenderGraph, Font},
-};
-
-use super::{GlobalGameData, Mode};
+use crate::{platform::Buttons, render::graph::RenderGraph};
 
-/// An action that a menu option can take.
-pub enum MenuAction {
-    /// Action switches to previous mode.
-    Previous,
-    /// Action loads new mode.
-    Switch(Box<dyn Fn(&GlobalGameData) -> Box<dyn Mode>>),
-    /// Action uses global data but does not switch mode.
-    Data(Box<dyn Fn(&GlobalGameData)>),
-}
-
-/// An option in the pause menu.
-pub struct MenuOption {
-    /// The name of the option.
-    name: String,
-    /// The action to take when this option is selected.
-    /// This function takes in the previous mode and returns a new mode to switch to.
-    action: MenuAction,
-}
-
-impl MenuOption {
-    pub fn previous(name: String) -> Self {
-        Self {
-            name,
-            action: MenuAction::Previous,
-        }
-    }
-
-    pub fn switch<F>(name: String, f: F) -> Self
-    where
-        F: Fn(&GlobalGameData) -> Box<dyn Mode> + 'static,
-    {
-        Self {
-            name,
-            action: MenuAction::Switch(Box::new(f)),
-        }
-    }
-
-    pub fn data<F>(name: String, f: F) -> Self
-    where
-        F: Fn(&GlobalGameData) + 'static,
-    {
-        Self {
-            name,
-            action: MenuAction::Data(Box::new(f)),
-        }
-    }
-}
+use super::{
+    menu::{Menu, MenuAction, MenuOption},
+    race::RaceMode,
+    GlobalGameData, Mode,
+};
 
 /// The pause menu mode.
 pub struct PauseMode {
     contains: Box<dyn Mode>,
-    options: Vec<MenuOption>,
-    selected: usize,
+    menu: Menu,
+    /// The heading shown above the options.
+    title: String,
 }
 
 impl PauseMode {
     pub fn new(contains: Box<dyn Mode>, options: Vec<MenuOption>) -> Self {
         Self {
             contains,
-            options,
-            selected: 0,
+            menu: Menu::new(options),
+            title: String::from("PAUSED"),
         }
     }
 
-    const CLIP_WIDTH: f32 = 240.0;
+    /// Show a different heading above the options.
+    #[must_use]
+    pub fn with_title(mut self, title: String) -> Self {
+        self.title = title;
+        self
+    }
+
+    /// Draw the options at a different scale, to fit longer options.
+    #[must_use]
+    pub fn with_menu_scale(mut self, scale: f32) -> Self {
+        self.menu.set_scale(scale);
+        self
+    }
 
-    const OPTION_SCALE: f32 = 3.0;
+    const CLIP_WIDTH: f32 = 240.0;
 }
 
 impl Mode for PauseMode {
@@ -97,20 +63,11 @@ impl Mode for PauseMode {
             return self.contains;
         }
 
-        if data.pressed.contains(Buttons::UP) && self.selected > 0 {
-            // previous option
-            self.selected -= 1;
-        } else if data.pressed.contains(Buttons::DOWN) && self.selected + 1 < self.options.len() {
-            // next option
-            self.selected += 1;
-        }
-
-        if data.pressed.contains(Buttons::OK) {
-            // select this option
-            let option = &self.options[self.selected];
-            return match &option.action {
+        if let Some(action) = self.menu.tick(data) {
+            return match action {
                 MenuAction::Previous => self.contains,
                 MenuAction::Switch(f) => f(data),
+                MenuAction::Wrap(f) => f(data, self.contains),
                 MenuAction::Data(f) => {
                     f(data);
                     self
@@ -143,19 +100,18 @@ impl Mode for PauseMode {
         graph.scissor(0.0, 0.0, menu_start, height_f32, new_graph);
         // divider line
         graph.line(menu_start, 0.0, menu_start, height_f32);
-        // draw "PAUSED" text
-        graph.text(menu_start + 16.0, 16.0, 4.0, String::from("PAUSED"));
+        // draw heading text
+        graph.text(menu_start + 16.0, 16.0, 4.0, self.title.clone());
         // draw options
-        let mut y = 64.0;
-        for (i, option) in self.options.iter().enumerate() {
-            let mut x = menu_start + 16.0;
-            // show cursor where we're selecting
-            if i == self.selected {
-                graph.text(x, y, Self::OPTION_SCALE, String::from(">"));
-                x += Font::GLYPH_SPACING * 2.0 * Self::OPTION_SCALE;
-            }
-            graph.text(x, y, Self::OPTION_SCALE, option.name.clone());
-            y += Self::OPTION_SCALE * 8.0;
-        }
+        self.menu.render(data, graph, menu_start + 16.0, 64.0);
+    }
+
+    fn race(&self) -> Option<&RaceMode> {
+        self.contains.race()
+    }
+
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    fn reload_asset(&mut self, name: &str) {
+        self.contains.reload_asset(name);
     }
 }
@@ -14,21 +14,30 @@
 //! You should have received a copy of the GNU General Public License
 //! along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::{cmp::Ordering, sync::Arc};
+
 use crate::{
-    assets::Asset,
+    assets::AssetError,
+    catalog::{load_course, DEFAULT_COURSE},
+    ghost::{Ghost, GhostFrame},
     linalg::{Length, Mtx, Quat, Vector},
     mode::Mode,
     octree::Octree,
-    platform::{Buttons, Controls},
+    platform::{Buttons, Controls, PlayerControls, MAX_PLAYERS},
     render::graph::{RenderGraph, RenderGraph3d},
+    replay::{Replay, ReplayFrame},
     spline::Spline,
+    timing::{format_ticks, TICKS_PER_SECOND},
     util::{Approach, Interpolate},
     vehicle::{garage::Garage, AIController, Controller, PlayerController, Vehicle},
 };
 
 use super::{
     loading::LoadingMode,
-    pause::{MenuOption, PauseMode},
+    menu::MenuOption,
+    options,
+    pause::PauseMode,
+    results::{ResultEntry, ResultsMode},
     title::TitleMode,
     GlobalGameData,
 };
@@ -37,16 +46,38 @@ const CAMERA_FOLLOW_DISTANCE: f32 = 2.5;
 const CAMERA_APPROACH_SPEED: f32 = 2.0;
 const CAMERA_UP_DISTANCE: f32 = 0.325;
 const STEERING_FACTOR: f32 = 0.25;
+/// How long to keep racing after the race ends before showing the results.
+const FINISH_DELAY: u16 = 3 * TICKS_PER_SECOND as u16;
+/// How long the countdown before the race lasts.
+const COUNTDOWN_LENGTH: u16 = 3 * TICKS_PER_SECOND as u16;
+/// Holding accelerate for at most this long before the start gives a boost.
+const BOOST_START_WINDOW: u16 = TICKS_PER_SECOND as u16 / 2;
+/// The speed given to a vehicle that gets a boost start.
+const BOOST_START_SPEED: f32 = 10.0;
+/// How far before the end of a point-to-point course the finish line is,
+/// leaving room for vehicles to stop.
+const FINISH_RUNOFF: f32 = 10.0;
 
 struct VehicleState {
     vehicle: Vehicle,
     prev_pos: Vector,
     prev_rot: Quat,
     prev_steering: f32,
+    /// The number of times the finish line was crossed, minus the number of
+    /// times it was crossed backwards.
+    laps: i32,
+    /// The spline offset seen on the previous tick, used to detect crossings.
+    prev_offset: f32,
+    /// The race time at which this vehicle finished, if it has.
+    finish_time: Option<u32>,
+    /// The countdown value when accelerate started being held before the start.
+    accel_since: Option<u16>,
+    /// The player controlling this vehicle, if any.
+    player: Option<usize>,
 }
 
 impl VehicleState {
-    pub fn new(pos: Vector, model_id: u16, controller: Box<dyn Controller>) -> Self {
+    pub fn new(pos: Vector, offset: f32, model_id: u16, controller: Box<dyn Controller>) -> Self {
         let vehicle = Vehicle::new(pos, model_id, controller);
 
         let prev_pos = vehicle.position;
@@ -58,9 +89,77 @@ impl VehicleState {
             prev_pos,
             prev_rot,
             prev_steering,
+            laps: 0,
+            prev_offset: offset,
+            finish_time: None,
+            accel_since: None,
+            player: None,
+        }
+    }
+
+    /// Track how long accelerate has been held during the countdown.
+    fn update_accel_since(&mut self, countdown: u16) {
+        if self.vehicle.pedal().amount() > 0.0 {
+            self.accel_since.get_or_insert(countdown);
+        } else {
+            self.accel_since = None;
+        }
+    }
+
+    /// Release the vehicle at the start of the race, giving it a boost if
+    /// accelerate was pressed at the right time.
+    fn start(&mut self) {
+        self.vehicle.locked = false;
+        if let Some(since) = self.accel_since {
+            if since <= BOOST_START_WINDOW {
+                self.vehicle.velocity += self.vehicle.forward_vector() * BOOST_START_SPEED;
+            }
+        }
+    }
+
+    /// Check if the vehicle crossed the finish line, and record the finish time
+    /// if the final lap was completed.
+    fn update_laps(&mut self, spline: &Spline, num_laps: u16, time: u32) {
+        let offset = self.vehicle.offset();
+        let delta = offset - self.prev_offset;
+        self.prev_offset = offset;
+        if !spline.is_closed() {
+            // point-to-point courses finish near the end, and vehicles stop
+            // once they're past it
+            if self.laps == 0 && offset >= spline.length - FINISH_RUNOFF {
+                self.laps = 1;
+                self.finish_time = Some(time);
+                self.vehicle.locked = true;
+            }
+            return;
+        }
+        // a jump of more than half the track means we wrapped around
+        if delta < spline.length * -0.5 {
+            self.laps += 1;
+        } else if delta > spline.length * 0.5 {
+            self.laps -= 1;
+        }
+        if self.finish_time.is_none() && self.laps >= i32::from(num_laps) {
+            self.finish_time = Some(time);
         }
     }
 
+    /// The total distance travelled along the track, including previous laps.
+    fn progress(&self, spline: &Spline) -> f32 {
+        self.laps as f32 * spline.length + self.prev_offset
+    }
+
+    /// The lap the vehicle is currently on, starting from one.
+    fn current_lap(&self, num_laps: u16) -> u16 {
+        (self.laps + 1).clamp(1, i32::from(num_laps)) as u16
+    }
+
+    /// The current position and rotation, including the steering roll.
+    fn transform(&self) -> GhostFrame {
+        let roll = Quat::axis_angle(&Vector::Z_AXIS, self.vehicle.steering * STEERING_FACTOR);
+        (self.vehicle.position, roll * self.vehicle.rotation)
+    }
+
     fn interpolate(&self, interp: f32) -> (Vector, Mtx) {
         let pos = self.prev_pos.interpolate(self.vehicle.position, interp);
 
@@ -125,10 +224,10 @@ impl VehicleState {
 }
 
 #[derive(Clone, Default)]
-struct CameraState {
-    pos: Vector,
-    target: Vector,
-    up: Vector,
+pub struct CameraState {
+    pub pos: Vector,
+    pub target: Vector,
+    pub up: Vector,
 }
 
 impl CameraState {
@@ -168,16 +267,255 @@ impl CameraState {
     }
 }
 
+/// A player's view of the race, drawn in its own part of the screen.
+struct PlayerView {
+    /// The vehicle the camera follows.
+    focus: usize,
+    camera: CameraState,
+    prev_camera: CameraState,
+}
+
+impl PlayerView {
+    fn new(focus: usize) -> Self {
+        Self {
+            focus,
+            camera: CameraState::default(),
+            prev_camera: CameraState::default(),
+        }
+    }
+
+    fn update(&mut self, states: &[VehicleState]) {
+        if let Some(state) = states.get(self.focus) {
+            self.prev_camera = self.camera.clone();
+            self.camera.update(&state.vehicle);
+        }
+    }
+
+    fn teleport(&mut self, states: &[VehicleState]) {
+        if let Some(state) = states.get(self.focus) {
+            self.camera.teleport(&state.vehicle);
+            // update prev camera as well
+            self.prev_camera = self.camera.clone();
+        }
+    }
+
+    fn interpolate(&self, interp: f32) -> CameraState {
+        CameraState {
+            pos: self.prev_camera.pos.interpolate(self.camera.pos, interp),
+            target: self
+                .prev_camera
+                .target
+                .interpolate(self.camera.target, interp),
+            up: self.prev_camera.up.interpolate(self.camera.up, interp),
+        }
+    }
+}
+
+/// Splits the screen into a region for each view, as (x, y, width, height).
+fn split_screen(views: usize, width: u16, height: u16) -> Vec<(f32, f32, u16, u16)> {
+    let half_width = width / 2;
+    let half_height = height / 2;
+    let left = 0.0;
+    let right = f32::from(half_width);
+    let top = 0.0;
+    let bottom = f32::from(half_height);
+    match views {
+        0 | 1 => vec![(left, top, width, height)],
+        // one above the other
+        2 => vec![
+            (left, top, width, half_height),
+            (left, bottom, width, half_height),
+        ],
+        // quadrants
+        n => [
+            (left, top, half_width, half_height),
+            (right, top, half_width, half_height),
+            (left, bottom, half_width, half_height),
+            (right, bottom, half_width, half_height),
+        ][..n.min(4)]
+            .to_vec(),
+    }
+}
+
+/// A summary of the state of a vehicle in a race.
+#[derive(Clone, Copy, Debug)]
+pub struct VehicleSummary {
+    /// The position of the vehicle.
+    pub position: Vector,
+    /// The direction the vehicle is facing.
+    pub forward: Vector,
+    /// The direction above the vehicle, away from the track.
+    pub up: Vector,
+    /// The point somewhat ahead on the track that the computer would steer
+    /// toward.
+    pub target: Vector,
+    /// The signed speed of the vehicle.
+    pub speed: f32,
+    /// The number of laps completed.
+    pub laps: i32,
+    /// The last seen offset along the spline.
+    pub offset: f32,
+    /// The race time at which the vehicle finished, if it has.
+    pub finish_time: Option<u32>,
+    /// If true, the vehicle fell off and is waiting to respawn.
+    pub respawning: bool,
+}
+
+/// Describes how a race should be set up.
+#[derive(Clone)]
+pub struct RaceSetup {
+    /// The name of the course asset.
+    pub course: String,
+    /// The name of the vehicle model to use.
+    pub model: String,
+    /// The number of laps in the race.
+    pub laps: u16,
+    /// The number of computer-controlled opponents.
+    pub opponents: u8,
+    /// If true, the player races alone against a ghost of their best lap.
+    pub time_trial: bool,
+    /// The number of local players, each with their own part of the screen.
+    pub players: u8,
+}
+
+impl Default for RaceSetup {
+    fn default() -> Self {
+        Self {
+            course: String::from(DEFAULT_COURSE),
+            model: String::from("default"),
+            laps: RaceMode::DEFAULT_LAPS,
+            opponents: 3,
+            time_trial: false,
+            players: 1,
+        }
+    }
+}
+
+impl RaceSetup {
+    /// The setup for a time trial on the default course.
+    #[must_use]
+    pub fn time_trial() -> Self {
+        Self {
+            opponents: 0,
+            time_trial: true,
+            players: 1,
+            ..Self::default()
+        }
+    }
+}
+
+/// Records the player's laps in a time trial and plays back the best one.
+struct TimeTrial {
+    /// The course name, used to find the ghost file.
+    course: String,
+    /// The model used to draw the ghost.
+    model_id: u16,
+    /// The best lap so far.
+    best: Option<Ghost>,
+    /// The frames of the lap in progress.
+    recording: Vec<GhostFrame>,
+    /// The race time the lap in progress started at.
+    lap_start: u32,
+    /// The number of laps recorded so far.
+    laps: i32,
+}
+
+impl TimeTrial {
+    fn new(course: String, model_id: u16) -> Self {
+        #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+        let best = Ghost::load(&Self::ghost_path(&course)).ok();
+        #[cfg(any(target_os = "horizon", target_arch = "wasm32"))]
+        let best = None;
+        Self {
+            course,
+            model_id,
+            best,
+            recording: vec![],
+            lap_start: 0,
+            laps: 0,
+        }
+    }
+
+    /// The file the ghost for a course is stored in, in the content directory.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    fn ghost_path(course: &str) -> std::path::PathBuf {
+        let name = course.strip_suffix(".bin").unwrap_or(course);
+        crate::assets::Asset::custom_path(&format!("{name}.ghost"))
+    }
+
+    /// Save the ghost for a course, creating the content directory if needed.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    fn save_ghost(course: &str, ghost: &Ghost) -> std::io::Result<()> {
+        let path = Self::ghost_path(course);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        ghost.save(&path)
+    }
+
+    /// Record the player's transform, keeping the lap if it was the fastest.
+    fn update(&mut self, state: &VehicleState, time: u32) {
+        if state.laps > self.laps {
+            self.laps = state.laps;
+            let lap_time = time - self.lap_start;
+            let frames = std::mem::take(&mut self.recording);
+            let is_best = match &self.best {
+                Some(best) => lap_time < best.time,
+                None => true,
+            };
+            if is_best {
+                let ghost = Ghost::new(lap_time, frames);
+                #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+                if let Err(e) = Self::save_ghost(&self.course, &ghost) {
+                    eprintln!("failed to save ghost: {e}");
+                }
+                self.best = Some(ghost);
+            }
+            self.lap_start = time;
+        }
+        self.recording.push(state.transform());
+    }
+
+    fn render(&self, time: u32, interp: f32, garage: &Garage, graph: &mut RenderGraph3d) {
+        if let (Some(best), Some(model)) = (&self.best, garage.get_model(self.model_id)) {
+            // the most recent frame was recorded on the previous tick
+            let tick = time.saturating_sub(self.lap_start + 1);
+            if let (Some(&(prev_pos, prev_rot)), Some(&(pos, rot))) =
+                (best.frame(tick.saturating_sub(1)), best.frame(tick))
+            {
+                let pos = prev_pos.interpolate(pos, interp);
+                let rot = Quat::slerp(prev_rot, rot, interp);
+                graph.mesh(pos, rot.into(), model.mesh.clone());
+            }
+        }
+    }
+}
+
 pub struct RaceMode {
     vehicle_states: Vec<VehicleState>,
 
     pub spline: Spline,
     pub octree: Octree,
 
-    camera: CameraState,
-    prev_camera: CameraState,
+    /// The view of each player.
+    views: Vec<PlayerView>,
 
-    pub camera_focus: usize,
+    /// The number of laps in the race.
+    num_laps: u16,
+    /// The number of ticks since the race started.
+    time: u32,
+    /// Counts down once the race is over, then the results are shown.
+    finish_timer: Option<u16>,
+    /// Vehicle indices ordered by race position, first place first.
+    standings: Vec<usize>,
+    /// The number of ticks until the race starts. Vehicles can't move until then.
+    countdown: u16,
+    /// The setup used to start the race, kept for restarting.
+    setup: RaceSetup,
+    /// The ghost state, if this is a time trial.
+    trial: Option<TimeTrial>,
+    /// The inputs given on each tick so far, for saving a replay.
+    recording: Vec<ReplayFrame>,
 }
 
 // (0, sin(PI / -8), cos(PI / -8))
@@ -189,99 +527,134 @@ fn adjust_normal(up: Vector, normal: Vector) -> Vector {
 }
 
 impl RaceMode {
+    /// The number of laps used if not otherwise specified.
+    pub const DEFAULT_LAPS: u16 = 3;
+
     #[must_use]
-    pub fn new(spline: Spline, octree: Octree, camera_focus: usize) -> Self {
+    pub fn new(spline: Spline, octree: Octree, setup: RaceSetup) -> Self {
         Self {
             vehicle_states: vec![],
             spline,
             octree,
-            camera: CameraState::default(),
-            prev_camera: CameraState::default(),
-            camera_focus,
+            views: vec![],
+            num_laps: setup.laps,
+            time: 0,
+            finish_timer: None,
+            standings: vec![],
+            countdown: COUNTDOWN_LENGTH,
+            setup,
+            trial: None,
+            recording: vec![],
         }
     }
 
     #[must_use]
-    pub fn initializing(garage: &Garage) -> LoadingMode<Self> {
-        let model = garage.get_id("default").unwrap();
-        LoadingMode::new(move || {
-            let spline = Spline::load(&mut Asset::load("course_test1.bin").unwrap()).unwrap();
-            let octree = Octree::new(&spline);
-            let mut mode = Self::new(spline, octree, 0);
-            // spawn player
-            let spawn = mode.spline.get_baked(0.0);
-            mode.spawn(spawn, model, Box::new(PlayerController::default()));
-            // spawn some other vehicles
-            let spawn = mode.spline.get_baked(5.0);
-            mode.spawn(spawn, model, Box::new(AIController::default()));
-            let spawn = mode.spline.get_baked(10.0);
-            mode.spawn(spawn, model, Box::new(AIController::default()));
-            let spawn = mode.spline.get_baked(15.0);
-            mode.spawn(spawn, model, Box::new(AIController::default()));
-            // set camera behind player
-            mode.teleport_camera();
-            mode
-        })
+    pub fn initializing(garage: &Garage, setup: RaceSetup) -> LoadingMode<Self> {
+        let model = garage.find_id(&setup.model);
+        LoadingMode::new(move || Self::load(model?, setup))
     }
 
-    pub fn spawn(&mut self, pos: Vector, model_id: u16, controller: Box<dyn Controller>) {
-        self.vehicle_states
-            .push(VehicleState::new(pos, model_id, controller));
+    /// Load the course and spawn the vehicles for a race. This may block.
+    pub fn load(model: u16, setup: RaceSetup) -> Result<Self, AssetError> {
+        let spline = load_course(&setup.course)?;
+        let octree = Octree::new(&spline);
+        let players = usize::from(setup.players).clamp(1, MAX_PLAYERS);
+        let opponents = usize::from(setup.opponents);
+        let trial = setup
+            .time_trial
+            .then(|| TimeTrial::new(setup.course.clone(), model));
+        let mut setup = setup;
+        // keep the setup consistent with the race, so replays match it
+        setup.players = players as u8;
+        // a race needs at least one lap to finish
+        setup.laps = setup.laps.max(1);
+        if !spline.is_closed() {
+            // point-to-point courses are only raced once
+            setup.laps = 1;
+        }
+        let mut mode = Self::new(spline, octree, setup);
+        mode.trial = trial;
+        // spawn players, then some other vehicles behind them. The last
+        // vehicle is still ahead of the finish line, so that no vehicle's
+        // first offset is mistaken for wrapping around the track.
+        let count = players + opponents;
+        for i in 0..count {
+            let offset = 5.0 * (count - i) as f32;
+            if i < players {
+                mode.spawn_player(offset, model, i);
+            } else {
+                mode.spawn(offset, model, Box::new(AIController::default()));
+            }
+        }
+        // set cameras behind players
+        mode.teleport_camera();
+        Ok(mode)
     }
 
-    fn update_camera_pos(&mut self) {
-        if self.camera_focus >= self.vehicle_states.len() {
-            return;
-        }
+    /// Spawn a vehicle at the given offset along the spline.
+    pub fn spawn(&mut self, offset: f32, model_id: u16, controller: Box<dyn Controller>) {
+        let pos = self.spline.get_baked(offset);
+        let mut state = VehicleState::new(pos, offset, model_id, controller);
+        state.vehicle.locked = self.countdown > 0;
+        self.vehicle_states.push(state);
+        self.update_standings();
+    }
 
-        self.prev_camera = self.camera.clone();
-        self.camera
-            .update(&self.vehicle_states[self.camera_focus].vehicle);
+    /// Spawn a vehicle controlled by a player, and give the player a view of it.
+    pub fn spawn_player(&mut self, offset: f32, model_id: u16, player: usize) {
+        self.spawn(offset, model_id, Box::new(PlayerController::default()));
+        let index = self.vehicle_states.len() - 1;
+        self.vehicle_states[index].player = Some(player);
+        self.views.push(PlayerView::new(index));
     }
 
-    pub fn teleport_camera(&mut self) {
-        if self.camera_focus >= self.vehicle_states.len() {
-            return;
-        }
+    /// Sort the vehicles by race position. Vehicles that have finished are
+    /// ranked by finish time, and the rest by how far they have travelled.
+    fn update_standings(&mut self) {
+        let states = &self.vehicle_states;
+        let spline = &self.spline;
+        self.standings = (0..states.len()).collect();
+        self.standings.sort_by(|&a, &b| {
+            let a = &states[a];
+            let b = &states[b];
+            match (a.finish_time, b.finish_time) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => b.progress(spline).total_cmp(&a.progress(spline)),
+            }
+        });
+    }
 
-        self.camera
-            .teleport(&self.vehicle_states[self.camera_focus].vehicle);
-        // update prev camera as well
-        self.prev_camera = self.camera.clone();
+    /// Returns the vehicle indices ordered by race position, first place first.
+    #[must_use]
+    pub fn standings(&self) -> &[usize] {
+        &self.standings
     }
-}
 
-impl Mode for RaceMode {
-    fn tick(mut self: Box<Self>, data: &GlobalGameData) -> Box<dyn Mode> {
-        if data.pressed.contains(Buttons::PAUSE) {
-            // return pause menu
-            return Box::new(PauseMode::new(
-                self,
-                vec![
-                    // restores previous state
-                    MenuOption::previous(String::from("resume")),
-                    // creates new race state
-                    MenuOption::switch(String::from("restart"), |data| {
-                        Box::new(RaceMode::initializing(&data.garage))
-                    }),
-                    // toggles walls
-                    MenuOption::data(String::from("toggle walls"), |data| {
-                        data.walls.set(!data.walls.get())
-                    }),
-                    // creates new title screen state
-                    MenuOption::switch(String::from("quit"), |_| Box::new(TitleMode)),
-                ],
-            ));
-        }
+    /// Returns the race position of a vehicle, starting from one.
+    #[must_use]
+    pub fn position(&self, index: usize) -> Option<usize> {
+        Some(self.standings.iter().position(|&i| i == index)? + 1)
+    }
+
+    /// Run a single tick of the race with the given inputs for each player.
+    pub fn step(&mut self, garage: &Garage, players: &PlayerControls, walls: bool) {
+        self.recording.push(ReplayFrame {
+            controls: *players,
+            walls,
+        });
         // check all vehicles that may need to respawn
-        let mut need_to_reset_camera = false;
+        let mut respawned = vec![];
         for (i, state) in self.vehicle_states.iter_mut().enumerate() {
-            if state.try_respawn() && i == self.camera_focus {
-                need_to_reset_camera = true;
+            if state.try_respawn() {
+                respawned.push(i);
             }
         }
-        if need_to_reset_camera {
-            self.teleport_camera();
+        for view in &mut self.views {
+            if respawned.contains(&view.focus) {
+                view.teleport(&self.vehicle_states);
+            }
         }
 
         // run physics on all vehicles
@@ -292,13 +665,11 @@ impl Mode for RaceMode {
         self.octree.reset_vehicles();
 
         for (i, state) in self.vehicle_states.iter_mut().enumerate() {
-            state.update(
-                &data.garage,
-                &self.spline,
-                &self.octree,
-                &data.controls,
-                data.walls.get(),
-            );
+            let controls = match state.player {
+                Some(player) => players[player],
+                None => Controls::default(),
+            };
+            state.update(garage, &self.spline, &self.octree, &controls, walls);
 
             total_translations.push(Vector::ZERO);
             original_velocity.push(state.vehicle.velocity);
@@ -345,8 +716,183 @@ impl Mode for RaceMode {
                 self.vehicle_states[j].vehicle.velocity += velocity;
             }
         }
+        // update lap counts
+        for state in &mut self.vehicle_states {
+            state.update_laps(&self.spline, self.num_laps, self.time);
+        }
+        self.update_standings();
+        // the clock only runs once the race has started
+        if self.countdown == 0 {
+            if let Some(trial) = &mut self.trial {
+                if let Some(view) = self.views.first() {
+                    trial.update(&self.vehicle_states[view.focus], self.time);
+                }
+            }
+            self.time += 1;
+        }
+        self.update_countdown();
         // now, run camera logic
         self.update_camera_pos();
+        // once the race is over, wait a bit before showing results
+        if let Some(timer) = &mut self.finish_timer {
+            *timer = timer.saturating_sub(1);
+        } else if self.is_race_over() {
+            self.finish_timer = Some(FINISH_DELAY);
+        }
+    }
+
+    /// Advance the countdown, releasing the vehicles once it reaches zero.
+    fn update_countdown(&mut self) {
+        if self.countdown == 0 {
+            return;
+        }
+        self.countdown -= 1;
+        for state in &mut self.vehicle_states {
+            if self.countdown == 0 {
+                state.start();
+            } else {
+                state.update_accel_since(self.countdown);
+            }
+        }
+    }
+
+    /// Returns true once the results should be shown.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.finish_timer == Some(0)
+    }
+
+    /// Stop recording ghosts, such as when watching a replay.
+    pub fn disable_ghost(&mut self) {
+        self.trial = None;
+    }
+
+    /// Returns the number of vehicles in the race.
+    #[must_use]
+    pub fn num_vehicles(&self) -> usize {
+        self.vehicle_states.len()
+    }
+
+    /// Returns the position of a vehicle.
+    #[must_use]
+    pub fn vehicle_position(&self, index: usize) -> Option<Vector> {
+        Some(self.vehicle_states.get(index)?.vehicle.position)
+    }
+
+    /// Returns a summary of the state of a vehicle.
+    #[must_use]
+    pub fn summary(&self, index: usize) -> Option<VehicleSummary> {
+        let state = self.vehicle_states.get(index)?;
+        Some(VehicleSummary {
+            position: state.vehicle.position,
+            forward: state.vehicle.forward_vector(),
+            up: state.vehicle.up_vector(),
+            target: state.vehicle.guidance_target(&self.spline),
+            speed: state.vehicle.signed_speed(),
+            laps: state.laps,
+            offset: state.vehicle.offset(),
+            finish_time: state.finish_time,
+            respawning: state.vehicle.respawn_timer.is_some(),
+        })
+    }
+
+    /// Returns the number of ticks since the race started.
+    #[must_use]
+    pub fn time(&self) -> u32 {
+        self.time
+    }
+
+    /// Build a replay of the race so far.
+    #[must_use]
+    pub fn replay(&self) -> Replay {
+        Replay {
+            setup: self.setup.clone(),
+            frames: self.recording.clone(),
+        }
+    }
+
+    /// Returns true if the race is over. This happens when every player has
+    /// finished, or when every vehicle finishes if there are no players.
+    fn is_race_over(&self) -> bool {
+        let has_players = self
+            .vehicle_states
+            .iter()
+            .any(|state| state.player.is_some());
+        self.vehicle_states
+            .iter()
+            .filter(|state| state.player.is_some() || !has_players)
+            .all(|state| state.finish_time.is_some())
+    }
+
+    /// Build the list of results in order of the final standings.
+    fn results(&self) -> Vec<ResultEntry> {
+        self.standings()
+            .iter()
+            .map(|&i| ResultEntry {
+                name: match self.vehicle_states[i].player {
+                    Some(_) if self.views.len() == 1 => String::from("PLAYER"),
+                    Some(player) => format!("P{}", player + 1),
+                    None => format!("CPU {}", i + 1 - self.views.len()),
+                },
+                time: self.vehicle_states[i].finish_time,
+            })
+            .collect()
+    }
+
+    fn update_camera_pos(&mut self) {
+        for view in &mut self.views {
+            view.update(&self.vehicle_states);
+        }
+    }
+
+    pub fn teleport_camera(&mut self) {
+        for view in &mut self.views {
+            view.teleport(&self.vehicle_states);
+        }
+    }
+
+    /// Returns the vehicle followed by the first player's camera.
+    #[must_use]
+    pub fn focus(&self) -> Option<usize> {
+        Some(self.views.first()?.focus)
+    }
+
+    /// Make the first player's camera follow another vehicle.
+    pub fn set_focus(&mut self, focus: usize) {
+        if let Some(view) = self.views.first_mut() {
+            view.focus = focus;
+            view.teleport(&self.vehicle_states);
+        }
+    }
+}
+
+impl Mode for RaceMode {
+    fn tick(mut self: Box<Self>, data: &GlobalGameData) -> Box<dyn Mode> {
+        if data.pressed.contains(Buttons::PAUSE) {
+            let setup = self.setup.clone();
+            // return pause menu
+            return Box::new(PauseMode::new(
+                self,
+                vec![
+                    // restores previous state
+                    MenuOption::previous(String::from("resume")),
+                    // creates new race state
+                    MenuOption::switch(String::from("restart"), move |data| {
+                        Box::new(RaceMode::initializing(&data.garage, setup.clone()))
+                    }),
+                    // toggles walls
+                    options::walls(),
+                    // creates new title screen state
+                    MenuOption::switch(String::from("quit"), |_| Box::new(TitleMode::new())),
+                ],
+            ));
+        }
+        let walls = data.settings.borrow().walls;
+        self.step(&data.garage, &data.players, walls);
+        if self.is_finished() {
+            let replay = Arc::new(self.replay());
+            return Box::new(ResultsMode::new(self.results(), replay));
+        }
         self
     }
 
@@ -355,29 +901,145 @@ impl Mode for RaceMode {
         interp: f32,
         data: &GlobalGameData,
         graph: &mut RenderGraph,
-        _width: u16,
-        _height: u16,
+        width: u16,
+        height: u16,
+    ) {
+        if self.views.len() == 1 {
+            let view = &self.views[0];
+            let camera = view.interpolate(interp);
+            self.render_view(
+                interp,
+                data,
+                graph,
+                width,
+                height,
+                &camera,
+                Some(view.focus),
+            );
+            return;
+        }
+        let regions = split_screen(self.views.len(), width, height);
+        for (view, &(x, y, width, height)) in self.views.iter().zip(&regions) {
+            let mut view_graph = RenderGraph::default();
+            let camera = view.interpolate(interp);
+            self.render_view(
+                interp,
+                data,
+                &mut view_graph,
+                width,
+                height,
+                &camera,
+                Some(view.focus),
+            );
+            graph.viewport(x, y, width, height, view_graph);
+        }
+        // divide the views
+        let center = f32::from(width / 2);
+        let middle = f32::from(height / 2);
+        let width = f32::from(width);
+        let height = f32::from(height);
+        graph.line(0.0, middle, width, middle);
+        if regions.len() > 2 {
+            graph.line(center, 0.0, center, height);
+        }
+    }
+
+    fn race(&self) -> Option<&RaceMode> {
+        Some(self)
+    }
+
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    fn reload_asset(&mut self, name: &str) {
+        if name != self.setup.course {
+            return;
+        }
+        // keep racing on the old course if the new one is broken
+        match load_course(name) {
+            Ok(spline) => {
+                self.octree = Octree::new(&spline);
+                self.spline = spline;
+                for state in &mut self.vehicle_states {
+                    state.vehicle.relocate(&self.spline, &self.octree);
+                    // measure the next lap crossing from the new course
+                    state.prev_offset = state.vehicle.offset();
+                }
+            }
+            Err(e) => eprintln!("failed to reload course: {e}"),
+        }
+    }
+}
+
+impl RaceMode {
+    /// Render the race as seen from the given camera, showing the first
+    /// player's status.
+    pub fn render_from(
+        &self,
+        interp: f32,
+        data: &GlobalGameData,
+        graph: &mut RenderGraph,
+        width: u16,
+        height: u16,
+        camera: &CameraState,
     ) {
-        let interp_camera_pos = self.prev_camera.pos.interpolate(self.camera.pos, interp);
-        let interp_camera_target = self
-            .prev_camera
-            .target
-            .interpolate(self.camera.target, interp);
-        let interp_camera_up = self.prev_camera.up.interpolate(self.camera.up, interp);
+        self.render_view(interp, data, graph, width, height, camera, self.focus());
+    }
 
-        let mut graph_3d =
-            RenderGraph3d::new(interp_camera_pos, interp_camera_target, interp_camera_up);
+    /// Render the race as seen from the given camera, showing the status of
+    /// the focused vehicle.
+    #[allow(clippy::too_many_arguments)]
+    fn render_view(
+        &self,
+        interp: f32,
+        data: &GlobalGameData,
+        graph: &mut RenderGraph,
+        width: u16,
+        height: u16,
+        camera: &CameraState,
+        focus: Option<usize>,
+    ) {
+        let mut graph_3d = RenderGraph3d::new(camera.pos, camera.target, camera.up);
 
         for state in &self.vehicle_states {
             state.render(interp, &data.garage, &mut graph_3d);
         }
 
-        self.spline.render(&mut graph_3d, data.walls.get());
+        if let Some(trial) = &self.trial {
+            trial.render(self.time, interp, &data.garage, &mut graph_3d);
+        }
+
+        self.spline
+            .render(&mut graph_3d, data.settings.borrow().walls);
 
-        if self.camera_focus < self.vehicle_states.len() {
-            let vehicle = &self.vehicle_states[self.camera_focus].vehicle;
-            let speed = vehicle.signed_speed();
+        let focused = focus.and_then(|focus| Some((focus, self.vehicle_states.get(focus)?)));
+        if let Some((focus, state)) = focused {
+            let speed = state.vehicle.signed_speed();
             graph.text(6.0, 6.0, 2.0, format!("SPEED {:.2}", speed));
+            let lap = state.current_lap(self.num_laps);
+            graph.text(6.0, 22.0, 2.0, format!("LAP {}/{}", lap, self.num_laps));
+            let time = state.finish_time.unwrap_or(self.time);
+            graph.text(6.0, 38.0, 2.0, format!("TIME {}", format_ticks(time)));
+            if let Some(best) = self.trial.as_ref().and_then(|trial| trial.best.as_ref()) {
+                graph.text(6.0, 54.0, 2.0, format!("BEST {}", format_ticks(best.time)));
+            } else if let Some(position) = self.position(focus) {
+                let count = self.vehicle_states.len();
+                graph.text(6.0, 54.0, 2.0, format!("POS {position}/{count}"));
+            }
+        }
+
+        let center = f32::from(width) * 0.5;
+        let middle = f32::from(height) * 0.5;
+        // each player sees when they finish, without waiting for the others
+        let finished = match focused {
+            Some((_, state)) => state.finish_time.is_some(),
+            None => self.finish_timer.is_some(),
+        };
+        if finished {
+            graph.text_centered(center, middle, 6.0, String::from("FINISH"));
+        } else if self.countdown > 0 {
+            let seconds = (self.countdown - 1) / u16::from(TICKS_PER_SECOND) + 1;
+            graph.text_centered(center, middle, 6.0, seconds.to_string());
+        } else if self.time < u32::from(TICKS_PER_SECOND) {
+            graph.text_centered(center, middle, 6.0, String::from("GO"));
         }
 
         graph.graph_3d(graph_3d);
@@ -0,0 +1,208 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use crate::{
+    linalg::Vector,
+    platform::Buttons,
+    render::{graph::RenderGraph, Font},
+    replay::Replay,
+    timing::TICK_DELTA,
+    util::Interpolate,
+};
+
+use super::{
+    loading::LoadingMode,
+    race::{CameraState, RaceMode},
+    title::TitleMode,
+    GlobalGameData, Mode,
+};
+
+/// The playback speeds that can be cycled through.
+const SPEEDS: [u8; 4] = [1, 2, 4, 8];
+/// How quickly the free camera orbits, in radians per second.
+const FREE_CAMERA_SPEED: f32 = 2.0;
+/// How far the free camera is from the vehicle.
+const FREE_CAMERA_DISTANCE: f32 = 6.0;
+/// How high the free camera is above the vehicle.
+const FREE_CAMERA_HEIGHT: f32 = 2.0;
+
+/// Plays back a recorded race by feeding the recorded inputs into a new race.
+pub struct ReplayMode {
+    /// The race being played back.
+    race: RaceMode,
+    /// The replay to read inputs from.
+    replay: Arc<Replay>,
+    /// The index of the next frame to play.
+    index: usize,
+    /// If true, playback is paused.
+    paused: bool,
+    /// Index into SPEEDS of the playback speed.
+    speed: usize,
+    /// The free camera angle around the vehicle, or None to use the race camera.
+    free_camera: Option<f32>,
+    /// The position the free camera is looking at.
+    target: Vector,
+    /// The previous position the free camera was looking at.
+    prev_target: Vector,
+}
+
+impl ReplayMode {
+    #[must_use]
+    pub fn initializing(data: &GlobalGameData, replay: Arc<Replay>) -> LoadingMode<Self> {
+        let model = data.garage.find_id(&replay.setup.model);
+        LoadingMode::new(move || {
+            let mut race = RaceMode::load(model?, replay.setup.clone())?;
+            // don't overwrite the ghost with the replayed laps
+            race.disable_ghost();
+            let target = race
+                .focus()
+                .and_then(|focus| race.vehicle_position(focus))
+                .unwrap_or_default();
+            Ok(Self {
+                race,
+                replay,
+                index: 0,
+                paused: false,
+                speed: 0,
+                free_camera: None,
+                target,
+                prev_target: target,
+            })
+        })
+    }
+
+    /// Returns true if every recorded frame has been played.
+    fn is_done(&self) -> bool {
+        self.index >= self.replay.frames.len()
+    }
+
+    /// Play the next frame of the replay.
+    fn step(&mut self, data: &GlobalGameData) {
+        if let Some(frame) = self.replay.frames.get(self.index) {
+            self.race.step(&data.garage, &frame.controls, frame.walls);
+            self.index += 1;
+        }
+    }
+}
+
+impl Mode for ReplayMode {
+    fn tick(mut self: Box<Self>, data: &GlobalGameData) -> Box<dyn Mode> {
+        if data.pressed.contains(Buttons::BACK) {
+            // stop watching
+            return Box::new(TitleMode::new());
+        }
+        if data.pressed.contains(Buttons::PAUSE) {
+            self.paused = !self.paused;
+        }
+        if data.pressed.contains(Buttons::OK) {
+            // cycle playback speed
+            self.speed = (self.speed + 1) % SPEEDS.len();
+        }
+        if data.pressed.contains(Buttons::UP) {
+            // toggle free camera
+            self.free_camera = match self.free_camera {
+                Some(_) => None,
+                None => Some(0.0),
+            };
+        }
+        if data.pressed.contains(Buttons::DOWN) {
+            if let Some(focus) = self.race.focus() {
+                // follow the next vehicle
+                self.race.set_focus((focus + 1) % self.race.num_vehicles());
+            }
+        }
+        if let Some(angle) = &mut self.free_camera {
+            *angle += data.controls.steering * FREE_CAMERA_SPEED * TICK_DELTA;
+        }
+        if !self.paused {
+            for _ in 0..SPEEDS[self.speed] {
+                self.step(data);
+            }
+        }
+        self.prev_target = self.target;
+        let focus = self.race.focus();
+        if let Some(target) = focus.and_then(|focus| self.race.vehicle_position(focus)) {
+            self.target = target;
+        }
+        self
+    }
+
+    fn render(
+        &self,
+        interp: f32,
+        data: &GlobalGameData,
+        graph: &mut RenderGraph,
+        width: u16,
+        height: u16,
+    ) {
+        // don't interpolate while paused, or the scene would jitter
+        let interp = if self.paused || self.is_done() {
+            1.0
+        } else {
+            interp
+        };
+        if let Some(angle) = self.free_camera {
+            let target = self.prev_target.interpolate(self.target, interp);
+            let offset = Vector::new(
+                angle.sin() * FREE_CAMERA_DISTANCE,
+                FREE_CAMERA_HEIGHT,
+                angle.cos() * FREE_CAMERA_DISTANCE,
+            );
+            let camera = CameraState {
+                pos: target + offset,
+                target,
+                up: Vector::Y_AXIS,
+            };
+            self.race
+                .render_from(interp, data, graph, width, height, &camera);
+        } else {
+            self.race.render(interp, data, graph, width, height);
+        }
+        // show playback state
+        let status = if self.is_done() {
+            String::from("REPLAY END")
+        } else if self.paused {
+            String::from("REPLAY PAUSED")
+        } else {
+            format!("REPLAY x{}", SPEEDS[self.speed])
+        };
+        let right = f32::from(width) - 6.0;
+        graph.text(
+            right - Font::GLYPH_SPACING * 2.0 * status.len() as f32,
+            6.0,
+            2.0,
+            status,
+        );
+        graph.text(
+            6.0,
+            f32::from(height) - 22.0,
+            2.0,
+            String::from("OK speed  UP camera  DOWN vehicle  BACK exit"),
+        );
+    }
+
+    fn race(&self) -> Option<&RaceMode> {
+        Some(&self.race)
+    }
+
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    fn reload_asset(&mut self, _name: &str) {
+        // the recorded inputs only play back exactly on the course they were
+        // recorded on, so keep using it
+    }
+}
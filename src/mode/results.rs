@@ -0,0 +1,114 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::sync::Arc;
+
+use crate::{render::graph::RenderGraph, replay::Replay, timing::format_ticks};
+
+use super::{
+    menu::{Menu, MenuAction, MenuOption},
+    replay::ReplayMode,
+    title::TitleMode,
+    GlobalGameData, Mode,
+};
+
+/// A single line in the results table.
+pub struct ResultEntry {
+    /// The name to display.
+    pub name: String,
+    /// The finish time, or None if the vehicle did not finish.
+    pub time: Option<u32>,
+}
+
+/// Shows the results of a race.
+pub struct ResultsMode {
+    entries: Vec<ResultEntry>,
+    menu: Menu,
+}
+
+impl ResultsMode {
+    #[must_use]
+    pub fn new(entries: Vec<ResultEntry>, replay: Arc<Replay>) -> Self {
+        let mut options = vec![MenuOption::switch(String::from("continue"), |_| {
+            Box::new(TitleMode::new())
+        })];
+
+        #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+        {
+            let replay = replay.clone();
+            options.push(MenuOption::data(String::from("save replay"), move |_| {
+                let path = std::path::Path::new(crate::replay::REPLAY_PATH);
+                if let Err(e) = replay.save(path) {
+                    eprintln!("failed to save replay: {e}");
+                }
+            }));
+        }
+
+        options.push(MenuOption::switch(
+            String::from("watch replay"),
+            move |data| Box::new(ReplayMode::initializing(data, replay.clone())),
+        ));
+
+        Self {
+            entries,
+            menu: Menu::new(options),
+        }
+    }
+
+    const ENTRY_SCALE: f32 = 3.0;
+}
+
+impl Mode for ResultsMode {
+    fn tick(mut self: Box<Self>, data: &GlobalGameData) -> Box<dyn Mode> {
+        match self.menu.tick(data) {
+            Some(MenuAction::Switch(f)) => f(data),
+            Some(MenuAction::Data(f)) => {
+                f(data);
+                self
+            }
+            // there's no previous mode to go back to
+            Some(MenuAction::Previous | MenuAction::Wrap(_)) | None => self,
+        }
+    }
+
+    fn render(
+        &self,
+        _interp: f32,
+        data: &GlobalGameData,
+        graph: &mut RenderGraph,
+        width: u16,
+        _height: u16,
+    ) {
+        let center = f32::from(width) * 0.5;
+        graph.text_centered(center, 16.0, 6.0, String::from("RESULTS"));
+        let mut y = 80.0;
+        for (i, entry) in self.entries.iter().enumerate() {
+            let time = if let Some(time) = entry.time {
+                format_ticks(time)
+            } else {
+                String::from("--:--.---")
+            };
+            graph.text(
+                32.0,
+                y,
+                Self::ENTRY_SCALE,
+                format!("{}. {:8} {}", i + 1, entry.name, time),
+            );
+            y += Self::ENTRY_SCALE * 8.0;
+        }
+        self.menu.render(data, graph, 32.0, y + 24.0);
+    }
+}
@@ -17,39 +17,111 @@
 use crate::{platform::Buttons, render::graph::RenderGraph};
 
 #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
-use super::editor::EditorMode;
+use std::sync::Arc;
 
-use super::{race::RaceMode, GlobalGameData, Mode};
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+use crate::replay::Replay;
 
-pub struct TitleMode;
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+use super::{editor::EditorMode, replay::ReplayMode};
 
-impl Mode for TitleMode {
-    fn tick(self: Box<Self>, data: &GlobalGameData) -> Box<dyn Mode> {
-        // if pressed pause, then consider that a signal to quit, except on web
-        // since it doesn't make logical sense to stop running on web
-        #[cfg(not(target_arch = "wasm32"))]
-        if data.pressed.contains(Buttons::BACK) {
-            data.should_run.set(false);
-        }
+use super::{
+    course_select::CourseSelectMode,
+    join::JoinMode,
+    menu::{Menu, MenuAction, MenuOption},
+    options,
+    race::{RaceMode, RaceSetup},
+    vehicle_select::VehicleSelectMode,
+    GlobalGameData, Mode,
+};
+
+pub struct TitleMode {
+    menu: Menu,
+}
+
+impl TitleMode {
+    #[must_use]
+    pub fn new() -> Self {
+        let mut options = vec![
+            MenuOption::switch(String::from("race"), |_| Box::new(JoinMode::new())),
+            MenuOption::switch(String::from("time trial"), |data| {
+                // whoever chose the time trial drives in it, rather than the
+                // players from the last race
+                let slot = data
+                    .slot_pressed
+                    .iter()
+                    .position(|pressed| pressed.contains(Buttons::OK))
+                    .unwrap_or(0);
+                data.seats.replace(vec![slot]);
+                Box::new(CourseSelectMode::load(|_, course| {
+                    let setup = RaceSetup {
+                        course: course.file.clone(),
+                        laps: course.meta.laps.unwrap_or(RaceMode::DEFAULT_LAPS),
+                        ..RaceSetup::time_trial()
+                    };
+                    Box::new(VehicleSelectMode::new(move |data, model| {
+                        let setup = RaceSetup {
+                            model: String::from(model),
+                            ..setup.clone()
+                        };
+                        Box::new(RaceMode::initializing(&data.garage, setup))
+                    }))
+                }))
+            }),
+        ];
+
+        options.push(MenuOption::switch(String::from("options"), |_| {
+            Box::new(options::menu(Box::new(TitleMode::new())))
+        }));
+
+        #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+        options.push(MenuOption::switch(String::from("watch replay"), |data| {
+            let path = std::path::Path::new(crate::replay::REPLAY_PATH);
+            match Replay::load(path) {
+                Ok(replay) => Box::new(ReplayMode::initializing(data, Arc::new(replay))),
+                Err(e) => {
+                    eprintln!("failed to load replay: {e}");
+                    Box::new(TitleMode::new())
+                }
+            }
+        }));
 
-        // load editor if pause pressed
         #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
-        if data.pressed.contains(Buttons::PAUSE) {
-            return Box::new(EditorMode::load());
+        options.push(MenuOption::switch(String::from("editor"), |_| {
+            Box::new(CourseSelectMode::load(|_, course| {
+                Box::new(EditorMode::load(course.file.clone()))
+            }))
+        }));
+
+        // it doesn't make logical sense to stop running on web
+        #[cfg(not(target_arch = "wasm32"))]
+        options.push(MenuOption::data(String::from("quit"), |data| {
+            data.should_run.set(false)
+        }));
+
+        Self {
+            menu: Menu::new(options),
         }
+    }
+}
 
-        if data.pressed.contains(Buttons::OK) {
-            // transition to race
-            Box::new(RaceMode::initializing(&data.garage))
-        } else {
-            self
+impl Mode for TitleMode {
+    fn tick(mut self: Box<Self>, data: &GlobalGameData) -> Box<dyn Mode> {
+        match self.menu.tick(data) {
+            Some(MenuAction::Switch(f)) => f(data),
+            Some(MenuAction::Data(f)) => {
+                f(data);
+                self
+            }
+            // there's no previous mode to go back to
+            Some(MenuAction::Previous | MenuAction::Wrap(_)) | None => self,
         }
     }
 
     fn render(
         &self,
         _interp: f32,
-        _data: &GlobalGameData,
+        data: &GlobalGameData,
         graph: &mut RenderGraph,
         width: u16,
         _height: u16,
@@ -57,17 +129,6 @@ impl Mode for TitleMode {
         // draw some text
         let center = f32::from(width) * 0.5;
         graph.text_centered(center, 32.0, 6.0, String::from("CONDUX"));
-        graph.text_centered(center, 120.0, 4.0, String::from("Press OK to start"));
-
-        #[cfg(not(target_arch = "wasm32"))]
-        graph.text_centered(center, 160.0, 4.0, String::from("Press Back to quit"));
-
-        #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
-        graph.text_centered(
-            center,
-            200.0,
-            4.0,
-            String::from("Press Pause to load editor"),
-        );
+        self.menu.render(data, graph, center - 96.0, 120.0);
     }
 }
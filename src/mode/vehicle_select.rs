@@ -0,0 +1,164 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{
+    linalg::{Mtx, Quat, Vector},
+    platform::Buttons,
+    render::graph::{RenderGraph, RenderGraph3d},
+    vehicle::Model,
+};
+
+use super::{title::TitleMode, GlobalGameData, Mode};
+
+/// Switches to the next mode once a vehicle has been chosen.
+type OnChosen = Box<dyn Fn(&GlobalGameData, &str) -> Box<dyn Mode>>;
+
+/// Reads a stat from a model.
+type Stat = fn(&Model) -> f32;
+
+/// The stats shown for each vehicle.
+const STATS: [(&str, Stat); 4] = [
+    ("speed", |model| model.speed),
+    ("accel", |model| model.acceleration),
+    ("handling", |model| model.handling),
+    ("grip", |model| model.anti_drift),
+];
+
+/// Lets the player choose a vehicle from the garage, comparing their stats.
+pub struct VehicleSelectMode {
+    /// The ID of the selected model.
+    selected: u16,
+    /// The angle the preview has spun to.
+    angle: f32,
+    /// Called with the chosen model's name.
+    on_chosen: OnChosen,
+}
+
+impl VehicleSelectMode {
+    /// How quickly the preview spins, in radians per tick.
+    const SPIN_SPEED: f32 = 0.02;
+
+    /// The width of a full stat bar.
+    const BAR_WIDTH: f32 = 160.0;
+
+    /// The height of a stat bar.
+    const BAR_HEIGHT: f32 = 12.0;
+
+    #[must_use]
+    pub fn new<F>(on_chosen: F) -> Self
+    where
+        F: Fn(&GlobalGameData, &str) -> Box<dyn Mode> + 'static,
+    {
+        Self {
+            selected: 0,
+            angle: 0.0,
+            on_chosen: Box::new(on_chosen),
+        }
+    }
+
+    /// Draw a bar filled to the given fraction.
+    fn render_bar(graph: &mut RenderGraph, x: f32, y: f32, fill: f32) {
+        let (w, h) = (Self::BAR_WIDTH, Self::BAR_HEIGHT);
+        graph.line(x, y, x + w, y);
+        graph.line(x + w, y, x + w, y + h);
+        graph.line(x + w, y + h, x, y + h);
+        graph.line(x, y + h, x, y);
+        // fill with horizontal lines
+        let fill_x = x + w * fill.clamp(0.0, 1.0);
+        let mut line_y = y + 2.0;
+        while line_y < y + h - 1.0 {
+            graph.line(x + 2.0, line_y, fill_x - 2.0, line_y);
+            line_y += 2.0;
+        }
+    }
+}
+
+impl Mode for VehicleSelectMode {
+    fn tick(mut self: Box<Self>, data: &GlobalGameData) -> Box<dyn Mode> {
+        let num_models = data.garage.num_models();
+        if data.pressed.contains(Buttons::BACK) || num_models == 0 {
+            return Box::new(TitleMode::new());
+        }
+        if data.pressed.contains(Buttons::LEFT) {
+            self.selected = (self.selected + num_models - 1) % num_models;
+        } else if data.pressed.contains(Buttons::RIGHT) {
+            self.selected = (self.selected + 1) % num_models;
+        }
+        if data.pressed.contains(Buttons::OK) {
+            if let Some(name) = data.garage.get_name(self.selected) {
+                return (self.on_chosen)(data, name);
+            }
+        }
+        self.angle += Self::SPIN_SPEED;
+        self
+    }
+
+    fn render(
+        &self,
+        interp: f32,
+        data: &GlobalGameData,
+        graph: &mut RenderGraph,
+        width: u16,
+        height: u16,
+    ) {
+        let garage = &data.garage;
+        let (model, name) = match (
+            garage.get_model(self.selected),
+            garage.get_name(self.selected),
+        ) {
+            (Some(model), Some(name)) => (model, name),
+            // about to return to the title screen
+            _ => return,
+        };
+        let width_f32 = f32::from(width);
+        let height_f32 = f32::from(height);
+        let center = width_f32 * 0.5;
+        graph.text(32.0, 16.0, 4.0, String::from("SELECT VEHICLE"));
+        graph.text_centered(center, 64.0, 3.0, format!("< {} >", name.to_uppercase()));
+        // spin the mesh in the middle of the screen
+        let preview_height = height.saturating_sub(240);
+        let mut preview = RenderGraph::default();
+        let mut graph_3d = RenderGraph3d::new(
+            Vector::new(0.0, 0.75, -2.5),
+            Vector::default(),
+            Vector::Y_AXIS,
+        );
+        let angle = self.angle + Self::SPIN_SPEED * interp;
+        let rotation = Mtx::from(Quat::axis_angle(&Vector::Y_AXIS, angle));
+        graph_3d.mesh(Vector::default(), rotation, model.mesh.clone());
+        preview.graph_3d(graph_3d);
+        graph.viewport(0.0, 96.0, width, preview_height, preview);
+        // compare each stat against the best vehicle's
+        let mut y = 96.0 + f32::from(preview_height) + 8.0;
+        let bar_x = center - Self::BAR_WIDTH * 0.5 + 48.0;
+        for (label, stat) in STATS {
+            let best = (0..garage.num_models())
+                .filter_map(|id| garage.get_model(id))
+                .map(stat)
+                .fold(0.0, f32::max);
+            let fill = if best > 0.0 { stat(model) / best } else { 0.0 };
+            graph.text(bar_x - 112.0, y, 2.0, String::from(label));
+            Self::render_bar(graph, bar_x, y, fill);
+            y += 24.0;
+        }
+        graph.text(
+            6.0,
+            height_f32 - 22.0,
+            2.0,
+            String::from("LEFT/RIGHT change  OK choose  BACK return"),
+        );
+    }
+}
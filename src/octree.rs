@@ -19,6 +19,8 @@ use crate::{linalg::Vector, spline::Spline, vehicle::Vehicle};
 const MAX_DEPTH: usize = 3;
 
 struct OctreeListEntry {
+    /// The route of a segment, which is unused for vehicles.
+    route: usize,
     index: usize,
     min: Vector,
     max: Vector,
@@ -51,18 +53,15 @@ pub struct Octree {
 fn select_which<'a>(
     entries: &'a [OctreeListEntry],
     point: &'a Vector,
-) -> impl Iterator<Item = usize> + 'a {
-    entries
-        .iter()
-        .filter(move |entry| {
-            entry.min.x <= point.x
-                && entry.max.x >= point.x
-                && entry.min.y <= point.y
-                && entry.max.y >= point.y
-                && entry.min.z <= point.z
-                && entry.max.z >= point.z
-        })
-        .map(|entry| entry.index)
+) -> impl Iterator<Item = &'a OctreeListEntry> + 'a {
+    entries.iter().filter(move |entry| {
+        entry.min.x <= point.x
+            && entry.max.x >= point.x
+            && entry.min.y <= point.y
+            && entry.max.y >= point.y
+            && entry.min.z <= point.z
+            && entry.max.z >= point.z
+    })
 }
 
 fn check_bounds(v: Vector, min: &mut Vector, max: &mut Vector) {
@@ -82,8 +81,15 @@ fn get_bounds(spline: &Spline, i: usize) -> (Vector, Vector) {
         &spline.baked[(i + 1) % spline.baked.len()],
     ] {
         let (up, right) = spline.get_up_right(b.offset);
-        let right = right * Spline::BOUNDS_RADIUS;
-        let above = up * Vehicle::MAX_GRAVITY_HEIGHT;
+        let right = right * (spline.get_radius(b.offset) + Spline::BOUNDS_MARGIN);
+        // tubes reach up to the far side of their wall
+        let height = if spline.in_tube(b.offset) {
+            Vehicle::MAX_GRAVITY_HEIGHT
+                .max(spline.get_radius(b.offset) * 2.0 + Vehicle::COLLISION_DEPTH)
+        } else {
+            Vehicle::MAX_GRAVITY_HEIGHT
+        };
+        let above = up * height;
         let below = up * -Vehicle::COLLISION_DEPTH;
         check_bounds(above - right + b.point, &mut min, &mut max);
         check_bounds(above + right + b.point, &mut min, &mut max);
@@ -201,25 +207,36 @@ impl Octree {
         // decide bounds
         let mut min = Vector::MAX;
         let mut max = Vector::MIN;
-        for i in 0..spline.baked.len() {
-            // get bounds of segment
-            let (segment_min, segment_max) = get_bounds(spline, i);
-            // update bounds
-            check_bounds(segment_min, &mut min, &mut max);
-            check_bounds(segment_max, &mut min, &mut max);
+        for route in 0..spline.num_routes() {
+            let route_spline = spline.route(route);
+            for i in 0..route_spline.num_segments() {
+                // get bounds of segment
+                let (segment_min, segment_max) = get_bounds(route_spline, i);
+                // update bounds
+                check_bounds(segment_min, &mut min, &mut max);
+                check_bounds(segment_max, &mut min, &mut max);
+            }
         }
         // build structure
         let mut root = build_octree(0);
-        // for each segment, figure out where to put it
-        for i in 0..spline.baked.len() {
-            let (segment_min, segment_max) = get_bounds(spline, i);
-            root.add(min, max, &segment_min, &segment_max, |node| {
-                node.segments.push(OctreeListEntry {
-                    index: i,
-                    min: segment_min,
-                    max: segment_max,
+        // for each segment of each route, figure out where to put it
+        for route in 0..spline.num_routes() {
+            let route_spline = spline.route(route);
+            for i in 0..route_spline.num_segments() {
+                // vehicles can't collide with sections over gaps
+                if route_spline.is_segment_in_gap(i) {
+                    continue;
+                }
+                let (segment_min, segment_max) = get_bounds(route_spline, i);
+                root.add(min, max, &segment_min, &segment_max, |node| {
+                    node.segments.push(OctreeListEntry {
+                        route,
+                        index: i,
+                        min: segment_min,
+                        max: segment_max,
+                    });
                 });
-            });
+            }
         }
         Self { min, max, root }
     }
@@ -244,6 +261,7 @@ impl Octree {
         self.root
             .add(self.min, self.max, &vehicle_min, &vehicle_max, |node| {
                 node.vehicles.push(OctreeListEntry {
+                    route: 0,
                     index,
                     min: vehicle_min,
                     max: vehicle_max,
@@ -260,8 +278,8 @@ impl Octree {
         loop {
             let which = search_existing_octree(point, &mut search_min, &mut search_max);
 
-            for index in select_which(&current.vehicles, point) {
-                result.push(index);
+            for entry in select_which(&current.vehicles, point) {
+                result.push(entry.index);
             }
 
             if let Some(children) = &current.children {
@@ -272,8 +290,9 @@ impl Octree {
         }
     }
 
+    /// Find the route and offset of the closest point on the spline.
     #[must_use]
-    pub fn find_closest_offset(&self, spline: &Spline, point: Vector) -> Option<f32> {
+    pub fn find_closest_offset(&self, spline: &Spline, point: Vector) -> Option<(usize, f32)> {
         let mut search_min = self.min;
         let mut search_max = self.max;
         let mut current = &self.root;
@@ -282,11 +301,13 @@ impl Octree {
         loop {
             let which = search_existing_octree(&point, &mut search_min, &mut search_max);
 
-            for index in select_which(&current.segments, &point) {
-                let (offset, dist_sq) = spline.get_offset_and_dist_sq(point, index);
+            for entry in select_which(&current.segments, &point) {
+                let (offset, dist_sq) = spline
+                    .route(entry.route)
+                    .get_offset_and_dist_sq(point, entry.index);
                 if dist_sq < best_dist_sq {
                     best_dist_sq = dist_sq;
-                    result = Some(offset);
+                    result = Some((entry.route, offset));
                 }
             }
 
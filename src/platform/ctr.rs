@@ -18,7 +18,7 @@ use ctru::prelude::*;
 
 use crate::render::context::Line2d;
 
-use super::{Buttons, Controls, Platform};
+use super::{Bindings, Buttons, Controls, Platform, SlotControls};
 
 static KEY_MAPPING: [ctru::services::hid::KeyPad; 7] = [
     ctru::services::hid::KeyPad::KEY_DUP,
@@ -193,7 +193,12 @@ impl Platform for CitroPlatform {
         240
     }
 
-    fn poll(&mut self) -> Controls {
+    fn default_bindings() -> Bindings {
+        // the buttons are fixed
+        Bindings::default()
+    }
+
+    fn poll(&mut self) -> SlotControls {
         self.hid.scan_input();
         let held = self.hid.keys_held();
         let mut buttons = Buttons::empty();
@@ -205,6 +210,13 @@ impl Platform for CitroPlatform {
         let mut circle_pos = ctru::services::hid::CirclePosition::default();
         let (x, _) = circle_pos.get();
         let steering = (f32::from(x) / 156.0).clamp(-1.0, 1.0);
-        Controls { buttons, steering }
+        // only one player slot on this platform
+        let mut result = SlotControls::default();
+        result[0] = Some(Controls {
+            buttons,
+            steering,
+            ..Controls::default()
+        });
+        result
     }
 }
@@ -33,6 +33,75 @@ bitflags! {
 pub struct Controls {
     pub buttons: Buttons,
     pub steering: f32,
+    /// How far the analog accelerator is pressed, from 0 to 1.
+    pub throttle: f32,
+    /// How far the analog brake is pressed, from 0 to 1.
+    pub brake: f32,
+}
+
+/// The number of buttons in [`Buttons`].
+pub const NUM_BUTTONS: usize = 7;
+
+/// The name of each button in bit order, used when saving bindings.
+pub const BUTTON_NAMES: [&str; NUM_BUTTONS] =
+    ["up", "down", "left", "right", "ok", "back", "pause"];
+
+/// The inputs bound to each button in bit order.
+pub type ButtonBindings = [Vec<String>; NUM_BUTTONS];
+
+/// The keys and gamepad buttons bound to each button. Inputs are named the
+/// way the platform names them.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Bindings {
+    /// The keys bound for each part of the keyboard, in player slot order.
+    pub keys: Vec<ButtonBindings>,
+    /// The buttons bound for every gamepad.
+    pub gamepad: ButtonBindings,
+}
+
+/// An input that was just pressed, used to capture new bindings.
+// without SDL, the headless build has no platform that reports raw inputs
+#[cfg_attr(not(any(feature = "sdl", target_arch = "wasm32")), allow(dead_code))]
+#[derive(Clone, PartialEq, Eq)]
+pub enum RawInput {
+    /// A key, by name.
+    Key(String),
+    /// A gamepad button, by name.
+    Button(String),
+}
+
+/// The maximum number of local players.
+pub const MAX_PLAYERS: usize = 4;
+
+/// The controls for each local player.
+pub type PlayerControls = [Controls; MAX_PLAYERS];
+
+/// The controls for each player slot, or None if nothing is connected to it.
+pub type SlotControls = [Option<Controls>; MAX_PLAYERS];
+
+/// The mouse position and buttons, used for the editor.
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+#[derive(Clone, Copy, Default)]
+pub struct MouseState {
+    /// The position in pixels from the left of the window.
+    pub x: i32,
+    /// The position in pixels from the top of the window.
+    pub y: i32,
+    /// If true, the left button is held.
+    pub left: bool,
+    /// If true, the right button is held.
+    pub right: bool,
+}
+
+/// How the game window should be shown.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct DisplayMode {
+    /// The window width when not fullscreen.
+    pub width: u16,
+    /// The window height when not fullscreen.
+    pub height: u16,
+    /// If true, the window covers the whole screen.
+    pub fullscreen: bool,
 }
 
 pub trait Platform {
@@ -40,6 +109,11 @@ pub trait Platform {
     where
         Self: Sized;
 
+    /// The bindings used if the player hasn't changed them.
+    fn default_bindings() -> Bindings
+    where
+        Self: Sized;
+
     #[cfg(not(target_arch = "wasm32"))]
     fn should_run(&self) -> bool;
 
@@ -51,7 +125,24 @@ pub trait Platform {
 
     fn height(&self) -> u16;
 
-    fn poll(&mut self) -> Controls;
+    fn poll(&mut self) -> SlotControls;
+
+    /// Change how the window is shown. Does nothing on platforms without a
+    /// resizable window.
+    fn set_display_mode(&mut self, _mode: DisplayMode) {
+        // default implementation if there is no window to change
+    }
+
+    /// Change which inputs are bound to each button. Does nothing on
+    /// platforms that can't be rebound.
+    fn set_bindings(&mut self, _bindings: &Bindings) {
+        // default implementation if bindings are fixed
+    }
+
+    /// Returns the most recent input pressed since this was last called.
+    fn last_input(&mut self) -> Option<RawInput> {
+        None
+    }
 }
 
 #[cfg(target_os = "horizon")]
@@ -60,14 +151,26 @@ pub mod ctr;
 #[cfg(target_arch = "wasm32")]
 pub mod web;
 
-#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+#[cfg(all(
+    feature = "sdl",
+    not(any(target_os = "horizon", target_arch = "wasm32"))
+))]
 pub mod sdl;
 
+#[cfg(not(any(feature = "sdl", target_os = "horizon", target_arch = "wasm32")))]
+pub mod null;
+
 #[cfg(target_os = "horizon")]
 pub type Impl = ctr::CitroPlatform;
 
 #[cfg(target_arch = "wasm32")]
 pub type Impl = web::WebPlatform;
 
-#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+#[cfg(all(
+    feature = "sdl",
+    not(any(target_os = "horizon", target_arch = "wasm32"))
+))]
 pub type Impl = sdl::SdlPlatform;
+
+#[cfg(not(any(feature = "sdl", target_os = "horizon", target_arch = "wasm32")))]
+pub type Impl = null::NullPlatform;
@@ -0,0 +1,70 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A platform with no window and no input, used on desktop when the SDL
+//! platform is disabled. The game stops as soon as it starts, so only the
+//! headless simulation is useful in such builds.
+
+use super::{Bindings, MouseState, Platform, SlotControls};
+
+pub struct NullPlatform {
+    width: u16,
+    height: u16,
+}
+
+impl NullPlatform {
+    pub fn get_mouse(&self) -> MouseState {
+        MouseState::default()
+    }
+
+    pub fn get_scroll_wheel(&self) -> i32 {
+        0
+    }
+}
+
+impl Platform for NullPlatform {
+    fn init(preferred_width: u16, preferred_height: u16) -> Self {
+        Self {
+            width: preferred_width,
+            height: preferred_height,
+        }
+    }
+
+    fn should_run(&self) -> bool {
+        false
+    }
+
+    fn buffer_line(&mut self, _x0: f32, _y0: f32, _x1: f32, _y1: f32) {}
+
+    fn end_frame(&mut self) {}
+
+    fn width(&self) -> u16 {
+        self.width
+    }
+
+    fn height(&self) -> u16 {
+        self.height
+    }
+
+    fn poll(&mut self) -> SlotControls {
+        SlotControls::default()
+    }
+
+    fn default_bindings() -> Bindings {
+        // there is no input to bind
+        Bindings::default()
+    }
+}
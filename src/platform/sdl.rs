@@ -16,9 +16,16 @@
 
 use sdl2::event::Event;
 
-use std::{collections::HashMap, error::Error, ffi::CString};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    ffi::CString,
+};
 
-use super::{Buttons, Controls, Platform};
+use super::{
+    Bindings, ButtonBindings, Buttons, Controls, DisplayMode, MouseState, Platform, RawInput,
+    SlotControls, MAX_PLAYERS, NUM_BUTTONS,
+};
 
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::style)]
@@ -391,9 +398,19 @@ pub struct SdlPlatform {
 
     should_run: bool,
 
-    keyboard_buttons: Buttons,
-
-    controller: Option<sdl2::controller::GameController>,
+    /// The keys currently held.
+    held_keys: HashSet<sdl2::keyboard::Keycode>,
+    /// The keys bound to each button, for each part of the keyboard.
+    key_bindings: Vec<[Vec<sdl2::keyboard::Keycode>; NUM_BUTTONS]>,
+    /// The controller buttons bound to each button.
+    button_bindings: [Vec<sdl2::controller::Button>; NUM_BUTTONS],
+    /// The most recent key or controller button pressed.
+    last_input: Option<RawInput>,
+
+    /// The controller assigned to each player slot.
+    controllers: [Option<sdl2::controller::GameController>; MAX_PLAYERS],
+    /// Controllers that were connected while every slot was taken.
+    spare_controllers: Vec<sdl2::controller::GameController>,
     /// Scroll wheel this frame.
     scroll_wheel: i32,
 
@@ -409,17 +426,30 @@ pub struct SdlPlatform {
     framebuffer: Framebuffer,
 }
 
-static KEYBOARD_MAPPING: [sdl2::keyboard::Keycode; 7] = [
-    sdl2::keyboard::Keycode::Up,
-    sdl2::keyboard::Keycode::Down,
-    sdl2::keyboard::Keycode::Left,
-    sdl2::keyboard::Keycode::Right,
-    sdl2::keyboard::Keycode::X,
-    sdl2::keyboard::Keycode::Z,
-    sdl2::keyboard::Keycode::Escape,
+/// Default mappings for each half of the keyboard, so two players can share it.
+static KEYBOARD_MAPPING: [[sdl2::keyboard::Keycode; NUM_BUTTONS]; 2] = [
+    [
+        sdl2::keyboard::Keycode::Up,
+        sdl2::keyboard::Keycode::Down,
+        sdl2::keyboard::Keycode::Left,
+        sdl2::keyboard::Keycode::Right,
+        sdl2::keyboard::Keycode::X,
+        sdl2::keyboard::Keycode::Z,
+        sdl2::keyboard::Keycode::Escape,
+    ],
+    [
+        sdl2::keyboard::Keycode::W,
+        sdl2::keyboard::Keycode::S,
+        sdl2::keyboard::Keycode::A,
+        sdl2::keyboard::Keycode::D,
+        sdl2::keyboard::Keycode::LShift,
+        sdl2::keyboard::Keycode::LCtrl,
+        sdl2::keyboard::Keycode::Tab,
+    ],
 ];
 
-static BUTTON_MAPPING: [sdl2::controller::Button; 7] = [
+/// Default mapping for controllers.
+static BUTTON_MAPPING: [sdl2::controller::Button; NUM_BUTTONS] = [
     sdl2::controller::Button::DPadUp,
     sdl2::controller::Button::DPadDown,
     sdl2::controller::Button::DPadLeft,
@@ -429,13 +459,28 @@ static BUTTON_MAPPING: [sdl2::controller::Button; 7] = [
     sdl2::controller::Button::Start,
 ];
 
-fn get_keycode_bitmask(keycode: sdl2::keyboard::Keycode) -> Buttons {
-    for (i, k) in KEYBOARD_MAPPING.iter().enumerate() {
-        if *k == keycode {
-            return Buttons::from_bits(1 << i).unwrap();
+/// Look up bound inputs by name, skipping any that SDL doesn't know.
+fn resolve_bindings<T, F>(bindings: &ButtonBindings, from_name: F) -> [Vec<T>; NUM_BUTTONS]
+where
+    F: Fn(&str) -> Option<T>,
+{
+    bindings
+        .each_ref()
+        .map(|names| names.iter().filter_map(|name| from_name(name)).collect())
+}
+
+/// Returns the buttons that have any of their bound inputs held.
+fn held_buttons<T, F>(bindings: &[Vec<T>; NUM_BUTTONS], is_held: F) -> Buttons
+where
+    F: Fn(&T) -> bool,
+{
+    let mut buttons = Buttons::empty();
+    for (i, bound) in bindings.iter().enumerate() {
+        if bound.iter().any(&is_held) {
+            buttons |= Buttons::from_bits(1 << i).unwrap();
         }
     }
-    Buttons::empty()
+    buttons
 }
 
 // (x, y) and (u, v) of the framebuffer quad
@@ -455,8 +500,56 @@ macro_rules! shader {
 }
 
 impl SdlPlatform {
-    pub fn get_mouse(&self) -> sdl2::mouse::MouseState {
-        self.event_pump.mouse_state()
+    /// Open a newly connected controller and assign it to the first free slot.
+    fn add_controller(&mut self, index: u32) {
+        let controller = match self.controller_ctx.open(index) {
+            Ok(controller) => controller,
+
+            Err(e) => {
+                eprintln!("failed to connect controller: {e}");
+                return;
+            }
+        };
+        // SDL may report controllers that were connected at startup twice
+        let id = controller.instance_id();
+        let is_open = self
+            .controllers
+            .iter()
+            .flatten()
+            .chain(&self.spare_controllers)
+            .any(|c| c.instance_id() == id);
+        if is_open {
+            return;
+        }
+        if let Some(slot) = self.controllers.iter_mut().find(|slot| slot.is_none()) {
+            *slot = Some(controller);
+        } else {
+            self.spare_controllers.push(controller);
+        }
+    }
+
+    /// Close a disconnected controller, giving its slot to a spare controller.
+    fn remove_controller(&mut self, id: u32) {
+        self.spare_controllers.retain(|c| c.instance_id() != id);
+        for slot in &mut self.controllers {
+            if slot.as_ref().map(|c| c.instance_id()) == Some(id) {
+                *slot = if self.spare_controllers.is_empty() {
+                    None
+                } else {
+                    Some(self.spare_controllers.remove(0))
+                };
+            }
+        }
+    }
+
+    pub fn get_mouse(&self) -> MouseState {
+        let state = self.event_pump.mouse_state();
+        MouseState {
+            x: state.x(),
+            y: state.y(),
+            left: state.left(),
+            right: state.right(),
+        }
     }
 
     pub fn get_scroll_wheel(&self) -> i32 {
@@ -495,7 +588,7 @@ impl Platform for SdlPlatform {
             gl::ClearColor(0.0, 0.0, 0.0, 1.0);
         }
 
-        Self {
+        let mut platform = Self {
             controller_ctx,
             event_pump,
             window,
@@ -505,9 +598,13 @@ impl Platform for SdlPlatform {
             height: preferred_height,
 
             should_run: true,
-            keyboard_buttons: Buttons::empty(),
+            held_keys: HashSet::new(),
+            key_bindings: vec![],
+            button_bindings: Default::default(),
+            last_input: None,
 
-            controller: None,
+            controllers: Default::default(),
+            spare_controllers: vec![],
 
             scroll_wheel: 0,
 
@@ -516,7 +613,9 @@ impl Platform for SdlPlatform {
             framebuffer_unit,
             texture: Texture::new(),
             framebuffer: Framebuffer::new(),
-        }
+        };
+        platform.set_bindings(&Self::default_bindings());
+        platform
     }
 
     fn should_run(&self) -> bool {
@@ -550,7 +649,9 @@ impl Platform for SdlPlatform {
         self.window.gl_swap_window();
         // accept events
         self.scroll_wheel = 0;
-        for event in self.event_pump.poll_iter() {
+        // collect first, as handling controller events needs the platform
+        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+        for event in events {
             match event {
                 Event::Window { win_event, .. } => match win_event {
                     sdl2::event::WindowEvent::Close => {
@@ -558,7 +659,7 @@ impl Platform for SdlPlatform {
                         self.should_run = false;
                     }
 
-                    sdl2::event::WindowEvent::Resized(x, y) => unsafe {
+                    sdl2::event::WindowEvent::SizeChanged(x, y) => unsafe {
                         gl::Viewport(0, 0, x, y);
                     },
 
@@ -567,16 +668,32 @@ impl Platform for SdlPlatform {
 
                 Event::KeyDown {
                     keycode: Some(keycode),
+                    repeat,
                     ..
                 } => {
-                    self.keyboard_buttons |= get_keycode_bitmask(keycode);
+                    self.held_keys.insert(keycode);
+                    if !repeat {
+                        self.last_input = Some(RawInput::Key(keycode.name()));
+                    }
                 }
 
                 Event::KeyUp {
                     keycode: Some(keycode),
                     ..
                 } => {
-                    self.keyboard_buttons &= !get_keycode_bitmask(keycode);
+                    self.held_keys.remove(&keycode);
+                }
+
+                Event::ControllerButtonDown { button, .. } => {
+                    self.last_input = Some(RawInput::Button(button.string()));
+                }
+
+                Event::ControllerDeviceAdded { which, .. } => {
+                    self.add_controller(which);
+                }
+
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.remove_controller(which);
                 }
 
                 Event::MouseWheel { y, direction, .. } => {
@@ -603,58 +720,94 @@ impl Platform for SdlPlatform {
         self.height
     }
 
-    fn poll(&mut self) -> Controls {
-        if let Some(controller) = &self.controller {
-            // close controller if not attached
-            if !controller.attached() {
-                self.controller = None;
-            }
+    fn default_bindings() -> Bindings {
+        Bindings {
+            keys: KEYBOARD_MAPPING
+                .iter()
+                .map(|mapping| mapping.map(|keycode| vec![keycode.name()]))
+                .collect(),
+            gamepad: BUTTON_MAPPING.map(|button| vec![button.string()]),
         }
-        // attempt to open a controller if not already opened
-        if self.controller.is_none() {
-            match self.controller_ctx.num_joysticks() {
-                Ok(n) => {
-                    for i in 0..n {
-                        if self.controller_ctx.is_game_controller(i) {
-                            match self.controller_ctx.open(i) {
-                                Ok(controller) => {
-                                    self.controller = Some(controller);
-                                }
-
-                                Err(e) => {
-                                    eprintln!("failed to connect controller: {e}");
-                                }
-                            }
-                        }
-                    }
-                }
+    }
 
-                Err(e) => {
-                    eprintln!("failed to query joysticks: {e}");
-                }
+    fn set_bindings(&mut self, bindings: &Bindings) {
+        self.key_bindings = bindings
+            .keys
+            .iter()
+            .map(|keys| resolve_bindings(keys, sdl2::keyboard::Keycode::from_name))
+            .collect();
+        self.button_bindings =
+            resolve_bindings(&bindings.gamepad, sdl2::controller::Button::from_string);
+    }
+
+    fn last_input(&mut self) -> Option<RawInput> {
+        self.last_input.take()
+    }
+
+    fn set_display_mode(&mut self, mode: DisplayMode) {
+        let fullscreen = if mode.fullscreen {
+            sdl2::video::FullscreenType::Desktop
+        } else {
+            sdl2::video::FullscreenType::Off
+        };
+        if let Err(e) = self.window.set_fullscreen(fullscreen) {
+            eprintln!("failed to change fullscreen: {e}");
+        }
+        if !mode.fullscreen {
+            if let Err(e) = self.window.set_size(mode.width.into(), mode.height.into()) {
+                eprintln!("failed to resize window: {e}");
             }
         }
-        let mut buttons = self.keyboard_buttons;
-        let mut steering = 0.0;
-        if let Some(controller) = &self.controller {
-            for (i, b) in BUTTON_MAPPING.iter().enumerate() {
-                if controller.button(*b) {
-                    buttons |= Buttons::from_bits(1 << i).unwrap();
-                }
+    }
+
+    fn poll(&mut self) -> SlotControls {
+        // each keyboard half is shared with the controller of the same player
+        let mut result = SlotControls::default();
+        for (i, controls) in result.iter_mut().enumerate() {
+            let keyboard = self
+                .key_bindings
+                .get(i)
+                .map(|keys| held_buttons(keys, |key| self.held_keys.contains(key)));
+            let controller = self.controllers[i].as_ref();
+            // nothing is connected to this slot
+            if keyboard.is_none() && controller.is_none() {
+                continue;
             }
-            let mut axis = controller.axis(sdl2::controller::Axis::LeftX);
-            if axis == -32768 {
-                axis = -32767;
+            let mut buttons = keyboard.unwrap_or_default();
+            let mut steering = 0.0;
+            let mut throttle = 0.0;
+            let mut brake = 0.0;
+            if let Some(controller) = controller {
+                buttons |= held_buttons(&self.button_bindings, |b| controller.button(*b));
+                let mut axis = controller.axis(sdl2::controller::Axis::LeftX);
+                if axis == -32768 {
+                    axis = -32767;
+                }
+                steering = f32::from(axis) / 32767.0;
+                // triggers only report positive values
+                throttle =
+                    f32::from(controller.axis(sdl2::controller::Axis::TriggerRight)) / 32767.0;
+                brake = f32::from(controller.axis(sdl2::controller::Axis::TriggerLeft)) / 32767.0;
             }
-            steering = f32::from(axis) / 32767.0;
-        } else {
-            // if no controller connected, use keyboard steering
-            if buttons.contains(Buttons::LEFT) {
-                steering = -1.0;
+            // use whichever steering is further from center, so a stick
+            // resting inside the deadzone doesn't hide the keyboard
+            let digital_steering: f32 = if buttons.contains(Buttons::LEFT) {
+                -1.0
             } else if buttons.contains(Buttons::RIGHT) {
-                steering = 1.0;
+                1.0
+            } else {
+                0.0
+            };
+            if digital_steering.abs() > steering.abs() {
+                steering = digital_steering;
             }
+            *controls = Some(Controls {
+                buttons,
+                steering,
+                throttle,
+                brake,
+            });
         }
-        Controls { buttons, steering }
+        result
     }
 }
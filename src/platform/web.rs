@@ -14,11 +14,18 @@
 //! You should have received a copy of the GNU General Public License
 //! along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{cell::Cell, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
+    rc::Rc,
+};
 
 use crate::render::context::Line2d;
 
-use super::{Buttons, Controls, Platform};
+use super::{
+    Bindings, ButtonBindings, Buttons, Controls, Platform, RawInput, SlotControls, MAX_PLAYERS,
+    NUM_BUTTONS,
+};
 
 use wasm_bindgen::prelude::*;
 
@@ -89,8 +96,14 @@ impl TouchElement {
 }
 
 pub struct WebPlatform {
-    /// buttons pressed by keyboard
-    keyboard_buttons: Rc<Cell<Buttons>>,
+    /// keys currently held
+    held_keys: Rc<RefCell<HashSet<String>>>,
+    /// the keys and gamepad buttons bound to each button
+    bindings: Rc<RefCell<Bindings>>,
+    /// the most recent key or gamepad button pressed
+    last_input: Rc<RefCell<Option<RawInput>>>,
+    /// the standard buttons held on each gamepad at the last poll
+    gamepad_held: [u32; MAX_PLAYERS],
     /// canvas to draw to
     canvas: web_sys::HtmlCanvasElement,
     /// 2d context for the canvas
@@ -121,7 +134,17 @@ fn is_pressed(button: &JsValue) -> bool {
     }
 }
 
-static KEYBOARD_MAPPING: [&str; 7] = [
+/// Returns how far an analog button is pressed, from 0 to 1.
+fn pressed_amount(button: &JsValue) -> f32 {
+    if let Some(button) = button.dyn_ref::<web_sys::GamepadButton>() {
+        button.value() as f32
+    } else {
+        0.0
+    }
+}
+
+/// Default mapping for the keyboard.
+static KEYBOARD_MAPPING: [&str; NUM_BUTTONS] = [
     "ArrowUp",
     "ArrowDown",
     "ArrowLeft",
@@ -131,15 +154,30 @@ static KEYBOARD_MAPPING: [&str; 7] = [
     "Escape",
 ];
 
-static BUTTON_MAPPING: [i32; 7] = [12, 13, 14, 15, 1, 0, 9];
+/// Default mapping for gamepads, as standard mapping indices.
+static BUTTON_MAPPING: [i32; NUM_BUTTONS] = [12, 13, 14, 15, 1, 0, 9];
 
-fn get_keycode_bitmask(keycode: &str) -> Buttons {
-    for (i, k) in KEYBOARD_MAPPING.iter().enumerate() {
-        if *k == keycode {
-            return Buttons::from_bits(1 << i).unwrap();
+/// The standard mapping indices of the right and left triggers.
+const THROTTLE_BUTTON: i32 = 7;
+const BRAKE_BUTTON: i32 = 6;
+
+/// Returns true if the key is bound to any button.
+fn is_key_bound(bindings: &Bindings, key: &str) -> bool {
+    bindings.keys.iter().flatten().flatten().any(|k| k == key)
+}
+
+/// Returns the buttons that have any of their bound inputs held.
+fn held_buttons<F>(bindings: &ButtonBindings, is_held: F) -> Buttons
+where
+    F: Fn(&str) -> bool,
+{
+    let mut buttons = Buttons::empty();
+    for (i, bound) in bindings.iter().enumerate() {
+        if bound.iter().any(|name| is_held(name)) {
+            buttons |= Buttons::from_bits(1 << i).unwrap();
         }
     }
-    Buttons::empty()
+    buttons
 }
 
 impl Platform for WebPlatform {
@@ -168,29 +206,35 @@ impl Platform for WebPlatform {
         let navigator = window.navigator();
         // get the mapping note element
         let gamepad_mapping_note = document.get_element_by_id("gamepad-mapping-note").unwrap();
-        // create keyboard buttons reference
-        let keyboard_buttons = Rc::new(Cell::new(Buttons::empty()));
+        // create keyboard references
+        let held_keys = Rc::new(RefCell::new(HashSet::new()));
+        let bindings = Rc::new(RefCell::new(Self::default_bindings()));
+        let last_input = Rc::new(RefCell::new(None));
         // create keydown listener
-        let keyboard_buttons_clone = keyboard_buttons.clone();
+        let held_keys_clone = held_keys.clone();
+        let bindings_clone = bindings.clone();
+        let last_input_clone = last_input.clone();
         let key_down =
             Closure::<dyn Fn(web_sys::KeyboardEvent)>::new(move |event: web_sys::KeyboardEvent| {
-                let bitmask = get_keycode_bitmask(&event.key());
-                if !bitmask.is_empty() {
+                let key = event.key();
+                if is_key_bound(&bindings_clone.borrow(), &key) {
                     event.prevent_default();
-                    let new_val = keyboard_buttons_clone.get() | bitmask;
-                    keyboard_buttons_clone.set(new_val);
                 }
+                if !event.repeat() {
+                    last_input_clone.replace(Some(RawInput::Key(key.clone())));
+                }
+                held_keys_clone.borrow_mut().insert(key);
             });
         // create keyup listener
-        let keyboard_buttons_clone = keyboard_buttons.clone();
+        let held_keys_clone = held_keys.clone();
+        let bindings_clone = bindings.clone();
         let key_up =
             Closure::<dyn Fn(web_sys::KeyboardEvent)>::new(move |event: web_sys::KeyboardEvent| {
-                let bitmask = get_keycode_bitmask(&event.key());
-                if !bitmask.is_empty() {
+                let key = event.key();
+                if is_key_bound(&bindings_clone.borrow(), &key) {
                     event.prevent_default();
-                    let new_val = keyboard_buttons_clone.get() & !bitmask;
-                    keyboard_buttons_clone.set(new_val);
                 }
+                held_keys_clone.borrow_mut().remove(&key);
             });
         // create pause button reference
         let pause_press = Rc::new(Cell::new(false));
@@ -211,7 +255,10 @@ impl Platform for WebPlatform {
             .unwrap();
         // return platform object
         Self {
-            keyboard_buttons,
+            held_keys,
+            bindings,
+            last_input,
+            gamepad_held: [0; MAX_PLAYERS],
             canvas,
             ctx,
             navigator,
@@ -226,6 +273,21 @@ impl Platform for WebPlatform {
         }
     }
 
+    fn default_bindings() -> Bindings {
+        Bindings {
+            keys: vec![KEYBOARD_MAPPING.map(|key| vec![String::from(key)])],
+            gamepad: BUTTON_MAPPING.map(|button| vec![button.to_string()]),
+        }
+    }
+
+    fn set_bindings(&mut self, bindings: &Bindings) {
+        self.bindings.replace(bindings.clone());
+    }
+
+    fn last_input(&mut self) -> Option<RawInput> {
+        self.last_input.take()
+    }
+
     fn buffer_line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) {
         self.lines.push(((x0, y0), (x1, y1)));
     }
@@ -262,20 +324,18 @@ impl Platform for WebPlatform {
         self.canvas.height() as _
     }
 
-    fn poll(&mut self) -> Controls {
-        let mut buttons = self.keyboard_buttons.get();
-        let mut steering = 0.0;
-
-        let mut current_gamepad = None;
-        let mut found_gamepad = false;
-        for gamepad in self.navigator.get_gamepads().unwrap().iter() {
-            if let Ok(gamepad) = gamepad.dyn_into::<web_sys::Gamepad>() {
-                found_gamepad = true;
-                if gamepad.mapping() == web_sys::GamepadMappingType::Standard {
-                    current_gamepad = Some(gamepad);
-                }
+    fn poll(&mut self) -> SlotControls {
+        let mut result = SlotControls::default();
+        // keyboard and touch controls belong to the first slot
+        let bindings = self.bindings.borrow();
+        let mut buttons = match bindings.keys.first() {
+            Some(keys) => {
+                let held_keys = self.held_keys.borrow();
+                held_buttons(keys, |key| held_keys.contains(key))
             }
-        }
+            None => Buttons::empty(),
+        };
+        let mut steering = 0.0;
 
         // does not support all buttons, but works for demo
         if let Some((x, y)) = self.virtual_analog.touch_position.get() {
@@ -292,32 +352,70 @@ impl Platform for WebPlatform {
             self.pause_press.set(false);
         }
 
-        self.gamepad_mapping_note
-            .set_attribute("hidden", "")
-            .unwrap();
-        if let Some(gamepad) = current_gamepad {
-            for (i, b) in BUTTON_MAPPING.iter().enumerate() {
-                if is_pressed(&gamepad.buttons().at(*b)) {
-                    buttons |= Buttons::from_bits(1 << i).unwrap();
+        if buttons.contains(Buttons::LEFT) {
+            steering = -1.0;
+        } else if buttons.contains(Buttons::RIGHT) {
+            steering = 1.0;
+        }
+        result[0] = Some(Controls {
+            buttons,
+            steering,
+            ..Controls::default()
+        });
+
+        // each gamepad with a standard mapping is assigned to the next slot
+        let mut found_unmapped = false;
+        let mut slot = 0;
+        for gamepad in self.navigator.get_gamepads().unwrap().iter() {
+            if let Ok(gamepad) = gamepad.dyn_into::<web_sys::Gamepad>() {
+                if gamepad.mapping() != web_sys::GamepadMappingType::Standard {
+                    found_unmapped = true;
+                    continue;
+                }
+                if slot >= MAX_PLAYERS {
+                    continue;
+                }
+                let controls = result[slot].get_or_insert_with(Controls::default);
+                let gamepad_buttons = gamepad.buttons();
+                controls.buttons |= held_buttons(&bindings.gamepad, |name| {
+                    name.parse()
+                        .map_or(false, |index| is_pressed(&gamepad_buttons.at(index)))
+                });
+                // find newly pressed buttons to capture bindings
+                let mut held = 0;
+                for index in 0..gamepad_buttons.length().min(32) {
+                    if is_pressed(&gamepad_buttons.get(index)) {
+                        held |= 1 << index;
+                    }
+                }
+                let pressed = held & !self.gamepad_held[slot];
+                if pressed != 0 {
+                    let index = pressed.trailing_zeros();
+                    self.last_input
+                        .replace(Some(RawInput::Button(index.to_string())));
+                }
+                self.gamepad_held[slot] = held;
+                slot += 1;
+                controls.throttle = pressed_amount(&gamepad.buttons().at(THROTTLE_BUTTON));
+                controls.brake = pressed_amount(&gamepad.buttons().at(BRAKE_BUTTON));
+                if let Some(axis) = gamepad.axes().at(0).as_f64() {
+                    if axis != 0.0 {
+                        controls.steering = axis as f32;
+                    }
                 }
             }
-            if let Some(axis) = gamepad.axes().at(0).as_f64() {
-                steering = axis as f32;
-            }
-        } else {
-            if found_gamepad {
-                self.gamepad_mapping_note
-                    .remove_attribute("hidden")
-                    .unwrap();
-            }
+        }
 
-            if buttons.contains(Buttons::LEFT) {
-                steering = -1.0;
-            } else if buttons.contains(Buttons::RIGHT) {
-                steering = 1.0;
-            }
+        if found_unmapped && slot == 0 {
+            self.gamepad_mapping_note
+                .remove_attribute("hidden")
+                .unwrap();
+        } else {
+            self.gamepad_mapping_note
+                .set_attribute("hidden", "")
+                .unwrap();
         }
 
-        Controls { buttons, steering }
+        result
     }
 }
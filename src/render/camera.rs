@@ -0,0 +1,77 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::linalg::{Mtx, Vector};
+
+use super::context::Point2d;
+
+/// A perspective camera, which converts between world and screen positions.
+pub struct Camera {
+    /// The position of the camera.
+    pos: Vector,
+    /// Transforms world directions into the camera's space.
+    mtx: Mtx,
+}
+
+impl Camera {
+    pub fn new(eye: Vector, at: Vector, up: Vector) -> Self {
+        Self {
+            pos: eye,
+            mtx: Mtx::looking_at(eye - at, up).transposed(),
+        }
+    }
+
+    /// Transform a point into the camera's space, where z is the depth in
+    /// front of the camera.
+    #[must_use]
+    pub fn transform(&self, point: Vector) -> Vector {
+        (point - self.pos) * self.mtx
+    }
+
+    /// Project a point in the camera's space onto a screen.
+    #[must_use]
+    pub fn screen_point(point: Vector, width: f32, height: f32) -> Point2d {
+        let scale = width.min(height);
+        let x = scale * (point.x / point.z) + (width / 2.0);
+        let y = (height / 2.0) - scale * (point.y / point.z);
+        (x, y)
+    }
+
+    /// Project a point onto a screen, or return None if it's behind the
+    /// camera.
+    #[must_use]
+    pub fn project(&self, point: Vector, width: f32, height: f32) -> Option<Point2d> {
+        let point = self.transform(point);
+        if point.z > 0.0 {
+            Some(Self::screen_point(point, width, height))
+        } else {
+            None
+        }
+    }
+
+    /// Find the point at a depth in front of the camera that appears at a
+    /// position on a screen.
+    #[must_use]
+    pub fn unproject(&self, x: f32, y: f32, depth: f32, width: f32, height: f32) -> Vector {
+        let scale = width.min(height);
+        let point = Vector::new(
+            (x - width / 2.0) * depth / scale,
+            (height / 2.0 - y) * depth / scale,
+            depth,
+        );
+        (point * self.mtx.transposed()) + self.pos
+    }
+}
@@ -14,10 +14,9 @@
 //! You should have received a copy of the GNU General Public License
 //! along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::{
-    linalg::{Mtx, Vector},
-    platform::Platform,
-};
+use crate::{linalg::Vector, platform::Platform};
+
+use super::camera::Camera;
 
 pub type Point2d = (f32, f32);
 pub type Line2d = (Point2d, Point2d);
@@ -151,10 +150,46 @@ impl<'a> RenderContext for ScissorContext<'a> {
     }
 }
 
+/// Offsets drawing into a region of the parent context, which acts as a
+/// smaller screen. Does not clip, so this should be used inside a scissor.
+pub struct ViewportContext<'a> {
+    parent: &'a mut dyn RenderContext,
+    x: f32,
+    y: f32,
+    width: u16,
+    height: u16,
+}
+
+impl<'a> ViewportContext<'a> {
+    pub fn new(parent: &'a mut dyn RenderContext, x: f32, y: f32, width: u16, height: u16) -> Self {
+        Self {
+            parent,
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+impl<'a> RenderContext for ViewportContext<'a> {
+    fn line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32) {
+        self.parent
+            .line(x0 + self.x, y0 + self.y, x1 + self.x, y1 + self.y);
+    }
+
+    fn width(&self) -> u16 {
+        self.width
+    }
+
+    fn height(&self) -> u16 {
+        self.height
+    }
+}
+
 pub struct RenderContext3d<'a> {
     context: &'a mut dyn RenderContext,
-    camera_pos: Vector,
-    camera_mtx: Mtx,
+    camera: Camera,
 }
 
 impl<'a> RenderContext3d<'a> {
@@ -163,15 +198,14 @@ impl<'a> RenderContext3d<'a> {
     pub fn new(context: &'a mut dyn RenderContext, eye: Vector, at: Vector, up: Vector) -> Self {
         Self {
             context,
-            camera_pos: eye,
-            camera_mtx: Mtx::looking_at(eye - at, up).transposed(),
+            camera: Camera::new(eye, at, up),
         }
     }
 
     pub fn line(&mut self, a: Vector, b: Vector) {
         // perform camera transform
-        let a = (a - self.camera_pos) * self.camera_mtx;
-        let b = (b - self.camera_pos) * self.camera_mtx;
+        let a = self.camera.transform(a);
+        let b = self.camera.transform(b);
         if a.z < Self::CUTOFF && b.z < Self::CUTOFF {
             // lies entirely behind camera, don't draw it
             return;
@@ -189,12 +223,9 @@ impl<'a> RenderContext3d<'a> {
         // adjust for screen res
         let width = f32::from(self.context.width());
         let height = f32::from(self.context.height());
-        let scale = width.min(height);
         // draw it
-        let x0 = scale * (a.x / a.z) + (width / 2.0);
-        let y0 = (height / 2.0) - scale * (a.y / a.z);
-        let x1 = scale * (b.x / b.z) + (width / 2.0);
-        let y1 = (height / 2.0) - scale * (b.y / b.z);
+        let (x0, y0) = Camera::screen_point(a, width, height);
+        let (x1, y1) = Camera::screen_point(b, width, height);
         self.context.line(x0, y0, x1, y1);
     }
 }
@@ -14,7 +14,7 @@
 //! You should have received a copy of the GNU General Public License
 //! along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::assets::Asset;
+use crate::assets::{Asset, AssetError, AssetErrorKind};
 
 use super::context::RenderContext;
 
@@ -25,7 +25,7 @@ struct Glyph {
 }
 
 impl Glyph {
-    fn load(asset: &mut Asset) -> Option<Glyph> {
+    fn load(asset: &mut Asset) -> Result<Glyph, AssetError> {
         let ranges = asset.read_byte()?;
         let num_points = (ranges & 15) as usize;
         let num_lines = (ranges >> 4) as usize;
@@ -36,14 +36,15 @@ impl Glyph {
         }
         let mut lines = Vec::with_capacity(num_lines);
         for _ in 0..num_lines {
+            let offset = asset.offset();
             let p = asset.read_byte()?;
             let (i, j) = (p & 15, p >> 4);
             if i as usize >= num_points || j as usize >= num_points {
-                return None;
+                return Err(asset.error_at(offset, AssetErrorKind::IndexOutOfRange));
             }
             lines.push((i, j));
         }
-        Some(Glyph { points, lines })
+        Ok(Glyph { points, lines })
     }
 
     fn render(&self, context: &mut dyn RenderContext, x: f32, y: f32, scale: f32) {
@@ -67,13 +68,13 @@ pub struct Font {
 impl Font {
     pub const GLYPH_SPACING: f32 = 5.0;
 
-    pub fn new() -> Option<Self> {
+    pub fn new() -> Result<Self, AssetError> {
         let mut asset = Asset::load("font.bin")?;
         let mut glyphs = vec![];
         for _ in 0..95 {
             glyphs.push(Glyph::load(&mut asset)?);
         }
-        Some(Self { glyphs })
+        Ok(Self { glyphs })
     }
 
     pub fn write(&self, context: &mut dyn RenderContext, mut x: f32, y: f32, scale: f32, s: &str) {
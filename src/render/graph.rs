@@ -19,7 +19,7 @@ use std::sync::Arc;
 use crate::linalg::{Mtx, Vector};
 
 use super::{
-    context::{Line2d, RenderContext, RenderContext3d, ScissorContext},
+    context::{Line2d, RenderContext, RenderContext3d, ScissorContext, ViewportContext},
     Font, Mesh,
 };
 
@@ -39,6 +39,13 @@ pub enum RenderNode {
         max_y: f32,
         graph: RenderGraph,
     },
+    Viewport {
+        x: f32,
+        y: f32,
+        width: u16,
+        height: u16,
+        graph: RenderGraph,
+    },
 }
 
 impl RenderNode {
@@ -57,6 +64,16 @@ impl RenderNode {
                 let mut new_ctx = ScissorContext::new(ctx, min_x, min_y, max_x, max_y);
                 graph.render(font, &mut new_ctx);
             }
+            Self::Viewport {
+                x,
+                y,
+                width,
+                height,
+                graph,
+            } => {
+                let mut new_ctx = ViewportContext::new(ctx, x, y, width, height);
+                graph.render(font, &mut new_ctx);
+            }
         }
     }
 }
@@ -90,6 +107,22 @@ impl RenderGraph {
         });
     }
 
+    /// Render a graph into a region of the screen, as if that region were the
+    /// whole screen. Anything drawn outside of the region is clipped.
+    pub fn viewport(&mut self, x: f32, y: f32, width: u16, height: u16, graph: RenderGraph) {
+        let mut inner = RenderGraph::default();
+        inner.nodes.push(RenderNode::Viewport {
+            x,
+            y,
+            width,
+            height,
+            graph,
+        });
+        let max_x = x + f32::from(width);
+        let max_y = y + f32::from(height);
+        self.scissor(x, y, max_x, max_y, inner);
+    }
+
     pub fn graph_3d(&mut self, graph: RenderGraph3d) {
         self.nodes.push(RenderNode::Graph3d(graph));
     }
@@ -15,7 +15,7 @@
 //! along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{
-    assets::Asset,
+    assets::{Asset, AssetError, AssetErrorKind},
     linalg::{Mtx, Vector},
 };
 
@@ -28,7 +28,7 @@ pub struct Mesh {
 }
 
 impl Mesh {
-    pub fn load(asset: &mut Asset) -> Option<Self> {
+    pub fn load(asset: &mut Asset) -> Result<Self, AssetError> {
         let num_vertices = asset.read_byte()?;
         let mut vertices = vec![];
         for _ in 0..num_vertices {
@@ -37,17 +37,21 @@ impl Mesh {
         let num_lines = asset.read_byte()?;
         let mut lines = vec![];
         for _ in 0..num_lines {
-            let x = asset.read_byte()?;
-            if x >= num_vertices {
-                return None;
-            }
-            let y = asset.read_byte()?;
-            if y >= num_vertices {
-                return None;
-            }
+            let x = Self::read_index(asset, num_vertices)?;
+            let y = Self::read_index(asset, num_vertices)?;
             lines.push((x, y));
         }
-        Some(Self { vertices, lines })
+        Ok(Self { vertices, lines })
+    }
+
+    /// Read the index of a vertex, checking that the vertex exists.
+    fn read_index(asset: &mut Asset, num_vertices: u8) -> Result<u8, AssetError> {
+        let offset = asset.offset();
+        let index = asset.read_byte()?;
+        if index >= num_vertices {
+            return Err(asset.error_at(offset, AssetErrorKind::IndexOutOfRange));
+        }
+        Ok(index)
     }
 
     pub fn render(&self, context: &mut RenderContext3d, translation: Vector, rotation: Mtx) {
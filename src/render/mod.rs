@@ -14,6 +14,7 @@
 //! You should have received a copy of the GNU General Public License
 //! along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+pub mod camera;
 pub mod context;
 pub mod font;
 pub mod graph;
@@ -0,0 +1,229 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::{self, Read, Write};
+
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+};
+
+use crate::{
+    mode::race::RaceSetup,
+    platform::{Buttons, Controls, PlayerControls, MAX_PLAYERS},
+};
+
+/// The inputs to the race on a single tick.
+#[derive(Clone, Copy)]
+pub struct ReplayFrame {
+    /// The state of each player's controls.
+    pub controls: PlayerControls,
+    /// If true, walls were enabled.
+    pub walls: bool,
+}
+
+/// A recording of a race that can be played back exactly.
+pub struct Replay {
+    /// The setup used to start the race.
+    pub setup: RaceSetup,
+    /// The inputs on each tick.
+    pub frames: Vec<ReplayFrame>,
+}
+
+/// Where the replay is saved to.
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+pub const REPLAY_PATH: &str = "condux.replay";
+
+const REPLAY_MAGIC: &[u8; 4] = b"CDXR";
+
+const REPLAY_VERSION: u8 = 1;
+
+const FLAG_WALLS: u8 = 1 << 0;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let [length] = read_bytes(reader)?;
+    let mut bytes = vec![0; usize::from(length)];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| invalid("invalid string"))
+}
+
+fn read_controls(reader: &mut impl Read) -> io::Result<Controls> {
+    let [buttons] = read_bytes(reader)?;
+    let steering = f32::from_le_bytes(read_bytes(reader)?);
+    let throttle = f32::from_le_bytes(read_bytes(reader)?);
+    let brake = f32::from_le_bytes(read_bytes(reader)?);
+    let buttons = Buttons::from_bits(buttons).ok_or_else(|| invalid("invalid buttons"))?;
+    Ok(Controls {
+        buttons,
+        steering,
+        throttle,
+        brake,
+    })
+}
+
+fn write_controls(writer: &mut impl Write, controls: &Controls) -> io::Result<()> {
+    writer.write_all(&[controls.buttons.bits()])?;
+    writer.write_all(&controls.steering.to_le_bytes())?;
+    writer.write_all(&controls.throttle.to_le_bytes())?;
+    writer.write_all(&controls.brake.to_le_bytes())
+}
+
+fn write_string(writer: &mut impl Write, s: &str) -> io::Result<()> {
+    let length = u8::try_from(s.len()).map_err(|_| invalid("string too long"))?;
+    writer.write_all(&[length])?;
+    writer.write_all(s.as_bytes())
+}
+
+impl Replay {
+    /// Write the replay in the replay file format.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(REPLAY_MAGIC)?;
+        writer.write_all(&[REPLAY_VERSION])?;
+        // race setup
+        write_string(writer, &self.setup.course)?;
+        write_string(writer, &self.setup.model)?;
+        writer.write_all(&self.setup.laps.to_le_bytes())?;
+        // only as many players as there are controls for
+        let players = usize::from(self.setup.players).min(MAX_PLAYERS);
+        writer.write_all(&[
+            self.setup.opponents,
+            u8::from(self.setup.time_trial),
+            players as u8,
+        ])?;
+        // inputs
+        let num_frames =
+            u32::try_from(self.frames.len()).map_err(|_| invalid("replay too long"))?;
+        writer.write_all(&num_frames.to_le_bytes())?;
+        for frame in &self.frames {
+            let flags = if frame.walls { FLAG_WALLS } else { 0 };
+            writer.write_all(&[flags])?;
+            for controls in &frame.controls[..players] {
+                write_controls(writer, controls)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a replay in the replay file format.
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        if &read_bytes::<4>(reader)? != REPLAY_MAGIC {
+            return Err(invalid("not a replay file"));
+        }
+        let [version] = read_bytes(reader)?;
+        if version != REPLAY_VERSION {
+            return Err(invalid("unsupported replay version"));
+        }
+        // race setup
+        let course = read_string(reader)?;
+        let model = read_string(reader)?;
+        let laps = u16::from_le_bytes(read_bytes(reader)?);
+        let [opponents, time_trial, players] = read_bytes(reader)?;
+        if players == 0 || usize::from(players) > MAX_PLAYERS {
+            return Err(invalid("invalid player count"));
+        }
+        if laps == 0 {
+            return Err(invalid("invalid lap count"));
+        }
+        let setup = RaceSetup {
+            course,
+            model,
+            laps,
+            opponents,
+            time_trial: time_trial != 0,
+            players,
+        };
+        // inputs
+        let num_frames = u32::from_le_bytes(read_bytes(reader)?);
+        let mut frames = vec![];
+        for _ in 0..num_frames {
+            let [flags] = read_bytes(reader)?;
+            let mut controls = PlayerControls::default();
+            for player in &mut controls[..usize::from(players)] {
+                *player = read_controls(reader)?;
+            }
+            frames.push(ReplayFrame {
+                controls,
+                walls: flags & FLAG_WALLS != 0,
+            });
+        }
+        Ok(Self { setup, frames })
+    }
+
+    /// Save the replay to a file.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Load a replay from a file.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::read(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mode::race::RaceMode;
+
+    fn replay(setup: RaceSetup) -> Replay {
+        Replay {
+            setup,
+            frames: vec![ReplayFrame {
+                controls: PlayerControls::default(),
+                walls: true,
+            }],
+        }
+    }
+
+    fn round_trip(replay: &Replay) -> io::Result<Replay> {
+        let mut bytes = vec![];
+        replay.write(&mut bytes)?;
+        Replay::read(&mut bytes.as_slice())
+    }
+
+    #[test]
+    fn write_read() {
+        let read = round_trip(&replay(RaceSetup::default())).unwrap();
+        assert_eq!(read.setup.laps, RaceMode::DEFAULT_LAPS);
+        assert_eq!(read.frames.len(), 1);
+        assert!(read.frames[0].walls);
+    }
+
+    #[test]
+    fn zero_laps_rejected() {
+        let setup = RaceSetup {
+            laps: 0,
+            ..RaceSetup::default()
+        };
+        assert!(round_trip(&replay(setup)).is_err());
+    }
+}
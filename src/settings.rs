@@ -0,0 +1,234 @@
+//! Condux - an antigravity racing game
+//! Copyright (C) 2023 spazzylemons
+//!
+//! This program is free software: you can redistribute it and/or modify
+//! it under the terms of the GNU General Public License as published by
+//! the Free Software Foundation, either version 3 of the License, or
+//! (at your option) any later version.
+//!
+//! This program is distributed in the hope that it will be useful,
+//! but WITHOUT ANY WARRANTY; without even the implied warranty of
+//! MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+//! GNU General Public License for more details.
+//!
+//! You should have received a copy of the GNU General Public License
+//! along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Options that persist between launches. On desktop these are stored in a
+//! config file, and on web in local storage.
+
+use std::{collections::HashSet, error::Error, fmt};
+
+use crate::platform::{Bindings, DisplayMode, Impl, Platform, BUTTON_NAMES};
+
+/// Where the settings are saved to.
+#[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+const SETTINGS_PATH: &str = "condux.cfg";
+
+/// The local storage key the settings are saved under.
+#[cfg(target_arch = "wasm32")]
+const SETTINGS_KEY: &str = "condux-settings";
+
+#[derive(Clone)]
+pub struct Settings {
+    /// If true, enable walls.
+    pub walls: bool,
+    /// Steering below this amount is ignored.
+    pub deadzone: f32,
+    /// The window width when not fullscreen.
+    pub width: u16,
+    /// The window height when not fullscreen.
+    pub height: u16,
+    /// If true, the window covers the whole screen.
+    pub fullscreen: bool,
+    /// The number of computer-controlled opponents in a race.
+    pub opponents: u8,
+    /// The keys and gamepad buttons bound to each button.
+    pub bindings: Bindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            walls: true,
+            deadzone: 0.03,
+            width: 640,
+            height: 480,
+            fullscreen: false,
+            opponents: 3,
+            bindings: Impl::default_bindings(),
+        }
+    }
+}
+
+impl Settings {
+    /// The most opponents that can be chosen.
+    pub const MAX_OPPONENTS: u8 = 7;
+    /// The narrowest the window can be.
+    pub const MIN_WIDTH: u16 = 320;
+    /// The shortest the window can be.
+    pub const MIN_HEIGHT: u16 = 240;
+
+    /// Parse settings, one "key = value" per line. Anything that can't be
+    /// understood keeps its default value. A binding may be repeated to bind
+    /// several inputs to the same button.
+    #[must_use]
+    pub fn parse(text: &str) -> Self {
+        let mut settings = Self::default();
+        // bindings that have replaced their defaults
+        let mut seen = HashSet::new();
+        for line in text.lines() {
+            let line = line.trim();
+            // skip blank lines and comments
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                let key = key.trim();
+                let value = value.trim();
+                if let Some(bound) = settings.binding_mut(key) {
+                    if seen.insert(key) {
+                        bound.clear();
+                    }
+                    // an empty value leaves the button unbound
+                    if !value.is_empty() {
+                        bound.push(String::from(value));
+                    }
+                    continue;
+                }
+                if let Err(e) = settings.set(key, value) {
+                    eprintln!("ignoring setting {key}: {e}");
+                }
+            }
+        }
+        settings
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        match key {
+            "walls" => self.walls = value.parse()?,
+            "deadzone" => self.deadzone = value.parse::<f32>()?.clamp(0.0, 1.0),
+            "width" => self.width = value.parse::<u16>()?.max(Self::MIN_WIDTH),
+            "height" => self.height = value.parse::<u16>()?.max(Self::MIN_HEIGHT),
+            "fullscreen" => self.fullscreen = value.parse()?,
+            "opponents" => self.opponents = value.parse::<u8>()?.min(Self::MAX_OPPONENTS),
+            _ => return Err("unknown setting".into()),
+        }
+        Ok(())
+    }
+
+    /// Find the list of inputs bound by a binding setting, named like
+    /// "keys1.ok" or "gamepad.ok".
+    fn binding_mut(&mut self, key: &str) -> Option<&mut Vec<String>> {
+        let (device, button) = key.split_once('.')?;
+        let index = BUTTON_NAMES.iter().position(|name| *name == button)?;
+        let buttons = if device == "gamepad" {
+            &mut self.bindings.gamepad
+        } else {
+            let part = device.strip_prefix("keys")?.parse::<usize>().ok()?;
+            self.bindings.keys.get_mut(part.checked_sub(1)?)?
+        };
+        Some(&mut buttons[index])
+    }
+
+    /// How the window should be shown.
+    #[must_use]
+    pub fn display_mode(&self) -> DisplayMode {
+        // the size may have been changed since it was parsed
+        DisplayMode {
+            width: self.width.max(Self::MIN_WIDTH),
+            height: self.height.max(Self::MIN_HEIGHT),
+            fullscreen: self.fullscreen,
+        }
+    }
+
+    /// Load the saved settings, or the defaults if there are none.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    #[must_use]
+    pub fn load() -> Self {
+        match std::fs::read_to_string(SETTINGS_PATH) {
+            Ok(text) => Self::parse(&text),
+            // no settings have been saved yet
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                eprintln!("failed to load settings: {e}");
+                Self::default()
+            }
+        }
+    }
+
+    /// Save the settings.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    pub fn save(&self) {
+        if let Err(e) = std::fs::write(SETTINGS_PATH, self.to_string()) {
+            eprintln!("failed to save settings: {e}");
+        }
+    }
+
+    /// Load the saved settings, or the defaults if there are none.
+    #[cfg(target_arch = "wasm32")]
+    #[must_use]
+    pub fn load() -> Self {
+        match local_storage().and_then(|storage| storage.get_item(SETTINGS_KEY).ok()?) {
+            Some(text) => Self::parse(&text),
+            None => Self::default(),
+        }
+    }
+
+    /// Save the settings.
+    #[cfg(target_arch = "wasm32")]
+    pub fn save(&self) {
+        if let Some(storage) = local_storage() {
+            // nothing to be done if storage is full or disabled
+            let _ = storage.set_item(SETTINGS_KEY, &self.to_string());
+        }
+    }
+
+    /// Settings are not saved on this platform.
+    #[cfg(target_os = "horizon")]
+    #[must_use]
+    pub fn load() -> Self {
+        Self::default()
+    }
+
+    /// Settings are not saved on this platform.
+    #[cfg(target_os = "horizon")]
+    pub fn save(&self) {}
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "walls = {}", self.walls)?;
+        writeln!(f, "deadzone = {}", self.deadzone)?;
+        writeln!(f, "width = {}", self.width)?;
+        writeln!(f, "height = {}", self.height)?;
+        writeln!(f, "fullscreen = {}", self.fullscreen)?;
+        writeln!(f, "opponents = {}", self.opponents)?;
+        for (part, buttons) in self.bindings.keys.iter().enumerate() {
+            write_bindings(f, &format!("keys{}", part + 1), buttons)?;
+        }
+        write_bindings(f, "gamepad", &self.bindings.gamepad)
+    }
+}
+
+/// Write one line per bound input, or an empty line if a button is unbound.
+fn write_bindings(
+    f: &mut fmt::Formatter<'_>,
+    device: &str,
+    buttons: &[Vec<String>],
+) -> fmt::Result {
+    for (name, bound) in BUTTON_NAMES.iter().zip(buttons) {
+        if bound.is_empty() {
+            writeln!(f, "{device}.{name} =")?;
+        }
+        for input in bound {
+            writeln!(f, "{device}.{name} = {input}")?;
+        }
+    }
+    Ok(())
+}
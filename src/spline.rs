@@ -20,7 +20,8 @@ use std::{
 };
 
 use crate::{
-    assets::Asset,
+    assets::{Asset, AssetError},
+    course::{Course, CourseBranch, CoursePoint, Section, Surface, SurfaceKind},
     linalg::{Length, Mtx, Vector},
     octree::Octree,
     render::graph::RenderGraph3d,
@@ -37,6 +38,7 @@ struct Point {
     control_mid: f32,
     tilt: f32,
     tilt_offset: f32,
+    radius: f32,
 }
 
 pub struct Baked {
@@ -45,20 +47,42 @@ pub struct Baked {
     pub offset: f32,
 }
 
+/// An alternate route that leaves the main loop and joins it again.
+pub struct Branch {
+    /// The path of the branch, which starts and ends on the main loop.
+    pub spline: Spline,
+    /// The offset along the main loop where the branch leaves it.
+    pub fork: f32,
+    /// The offset along the main loop where the branch joins it again.
+    pub merge: f32,
+}
+
 pub struct Spline {
     /// The control points.
     points: Vec<Point>,
+    /// If true, the spline loops back to its first point.
+    closed: bool,
     /// The baked points.
     pub baked: Vec<Baked>,
     /// The total tilt, used for interpolation.
     total_tilt: f32,
     /// The approximate length of the spline.
     pub length: f32,
+    /// The surface zones.
+    surfaces: Vec<Surface>,
+    /// The sections with no floor.
+    gaps: Vec<Section>,
+    /// The sections where the track wraps around into a tube.
+    tubes: Vec<Section>,
+    /// The alternate routes off of the main loop.
+    branches: Vec<Branch>,
 
     /// The points to render for the floor.
     render_floor: Arc<Vec<(Vector, Vector)>>,
     /// The points to render for the walls.
     render_walls: Arc<Vec<(Vector, Vector)>>,
+    /// The points to render for the surface zones.
+    render_surfaces: Arc<Vec<(Vector, Vector)>>,
 }
 
 pub struct CollisionState {
@@ -70,46 +94,108 @@ pub struct CollisionState {
     pub height: f32,
     /// The horizontal offset on the spline.
     pub horizontal: f32,
-    /// The offset of the closest point.
+    /// The radius of the spline at the closest point.
+    pub radius: f32,
+    /// The route the closest point is on.
+    pub route: usize,
+    /// The offset of the closest point along its route.
     pub offset: f32,
+    /// The kind of surface at the collision point, if it's not plain track.
+    pub surface: Option<SurfaceKind>,
+    /// If true, the collision point is on the wall of a tube, and the
+    /// horizontal offset is the distance around it from the bottom.
+    pub tube: bool,
 }
 
 impl Spline {
-    /// The radius of the spline.
+    /// The radius of the spline where the course doesn't specify one.
     pub const TRACK_RADIUS: f32 = 2.0;
-    /// The extended spline radius for bounds checks.
-    pub const BOUNDS_RADIUS: f32 = 5.0;
+    /// How far past the radius of the spline bounds checks extend.
+    pub const BOUNDS_MARGIN: f32 = 3.0;
     /// The height of the walls on the side.
     pub const WALL_HEIGHT: f32 = 0.25;
+    /// The number of sides to draw tubes with.
+    const TUBE_SIDES: usize = 12;
 
     const MAX_BAKE_DEPTH: usize = 5;
 
-    pub fn load(asset: &mut Asset) -> Option<Self> {
-        // number of points
-        let num_points = asset.read_byte()?;
-        if num_points < 3 {
+    /// Load a course of any version.
+    pub fn load(asset: &mut Asset) -> Result<Self, AssetError> {
+        Ok(Self::new(&Course::load(asset)?))
+    }
+
+    /// Build a spline through the points of a course, which must have been
+    /// checked to form a valid course.
+    #[must_use]
+    pub fn new(course: &Course) -> Self {
+        let mut spline = Self::build(&course.points, course.closed);
+        spline.surfaces = course.surfaces.clone();
+        spline.gaps = course.gaps.clone();
+        spline.tubes = course.tubes.clone();
+        spline.prerender();
+        spline.branches = course
+            .branches
+            .iter()
+            .filter_map(|branch| spline.build_branch(branch))
+            .collect();
+        spline
+    }
+
+    /// Build a branch off of the main loop. Returns None if the points of the
+    /// branch don't leave room for a spline.
+    fn build_branch(&self, branch: &CourseBranch) -> Option<Branch> {
+        let end_point = |offset: f32| CoursePoint {
+            position: self.get_baked(offset),
+            tilt: self.get_tilt(offset).rem_euclid(TAU),
+            width: self.get_radius(offset) * 2.0,
+        };
+        let fork = end_point(branch.fork);
+        let merge = end_point(branch.merge);
+        let mut points = vec![fork];
+        for point in &branch.points {
+            // points in the same place would divide by zero
+            let last = &points[points.len() - 1];
+            if last.position.dist(point.position) > f32::EPSILON
+                && merge.position.dist(point.position) > f32::EPSILON
+            {
+                points.push(CoursePoint { ..*point });
+            }
+        }
+        if points.len() < 2 {
             return None;
         }
-        let mut points = vec![];
-        // TODO div by zero checks?
-        for _ in 0..num_points {
-            let point = asset.read_vector()?;
-            let tilt = (f32::from(asset.read_byte()?) / 256.0) * TAU;
-            points.push(Point {
-                point,
+        points.push(merge);
+        let mut spline = Self::build(&points, false);
+        spline.prerender();
+        Some(Branch {
+            spline,
+            fork: branch.fork.rem_euclid(self.length),
+            merge: branch.merge.rem_euclid(self.length),
+        })
+    }
+
+    /// Build a spline through points, either as a loop or as a path with two
+    /// ends, without preparing it for rendering.
+    fn build(course_points: &[CoursePoint], closed: bool) -> Self {
+        let num_points = course_points.len();
+        // paths have one less segment than loops with the same points
+        let num_segments = if closed { num_points } else { num_points - 1 };
+        let mut points: Vec<Point> = course_points
+            .iter()
+            .map(|point| Point {
+                point: point.position,
                 control: Vector::default(),
                 control_mid: 0.0,
-                tilt,
+                tilt: point.tilt,
                 tilt_offset: 0.0,
-            });
-        }
+                radius: point.width * 0.5,
+            })
+            .collect();
         // fix tilts
         let mut total_tilt = points[0].tilt;
-        for i in 0..num_points {
-            let delta = (points[usize::from((i + 1) % num_points)].tilt
-                - points[usize::from(i)].tilt)
-                .rem_euclid(TAU);
-            points[usize::from(i)].tilt = total_tilt;
+        for i in 0..num_segments {
+            let delta = (points[(i + 1) % num_points].tilt - points[i].tilt).rem_euclid(TAU);
+            points[i].tilt = total_tilt;
             if delta <= PI {
                 // move up
                 total_tilt += delta;
@@ -118,17 +204,19 @@ impl Spline {
                 total_tilt += delta - TAU;
             }
         }
+        if !closed {
+            points[num_points - 1].tilt = total_tilt;
+        }
         // generate bezier control points
-        for i in 0..num_points {
-            let a = usize::from(i);
-            let b = usize::from((i + 1) % num_points);
-            let c = usize::from((i + 2) % num_points);
+        let num_curves = if closed { num_points } else { num_points - 2 };
+        for a in 0..num_curves {
+            let b = (a + 1) % num_points;
+            let c = (a + 2) % num_points;
             let pa = points[a].point;
             let pb = points[b].point;
             let pc = points[c].point;
             let da = pa.dist(pb);
             let db = pb.dist(pc);
-            // TODO handle potential divs by zero in this area
             let mid = da / (da + db);
             let fac_a = (mid - 1.0) / (2.0 * mid);
             let fac_b = 1.0 / (2.0 * mid * (1.0 - mid));
@@ -138,38 +226,166 @@ impl Spline {
         }
         let mut spline = Self {
             points,
+            closed,
             baked: vec![],
             total_tilt,
             length: 0.0,
+            surfaces: vec![],
+            gaps: vec![],
+            tubes: vec![],
+            branches: vec![],
 
             render_floor: Arc::new(vec![]),
             render_walls: Arc::new(vec![]),
+            render_surfaces: Arc::new(vec![]),
         };
         // for each point, recursively find points to bake
-        for i in 0..num_points {
+        for i in 0..num_segments {
             // bake at control point
-            spline.add_baked(f32::from(i));
+            spline.add_baked(i as f32);
             // add length to tilt offsets
-            spline.points[usize::from(i)].tilt_offset = spline.length;
+            spline.points[i].tilt_offset = spline.length;
             // bake in between
             spline.bake_recursive(i, 0.0, 1.0, 0);
         }
-        // finish off length measurement
-        let final_length = spline.baked[0]
-            .point
-            .dist(spline.baked[spline.baked.len() - 1].point);
-        spline.length += final_length;
-        // build render info
-        spline.prerender();
-        // all good
-        Some(spline)
+        if closed {
+            // finish off length measurement
+            let final_length = spline.baked[0]
+                .point
+                .dist(spline.baked[spline.baked.len() - 1].point);
+            spline.length += final_length;
+        } else {
+            // bake the end of the path
+            spline.add_baked((num_points - 1) as f32);
+            spline.points[num_points - 1].tilt_offset = spline.length;
+        }
+        spline
+    }
+
+    /// Check if the spline loops back to its first point.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Get the number of baked segments, which is one less than the number of
+    /// baked points for paths.
+    #[must_use]
+    pub fn num_segments(&self) -> usize {
+        if self.closed {
+            self.baked.len()
+        } else {
+            self.baked.len() - 1
+        }
+    }
+
+    /// Get the number of routes, which are the main loop followed by each
+    /// branch.
+    #[must_use]
+    pub fn num_routes(&self) -> usize {
+        self.branches.len() + 1
+    }
+
+    /// Get the branch that a route follows, or None for the main loop. Routes
+    /// that don't exist, such as after the course is reloaded with fewer
+    /// branches, are treated as the main loop.
+    fn route_branch(&self, route: usize) -> Option<&Branch> {
+        route
+            .checked_sub(1)
+            .and_then(|index| self.branches.get(index))
+    }
+
+    /// Get the spline of a route.
+    #[must_use]
+    pub fn route(&self, route: usize) -> &Self {
+        match self.route_branch(route) {
+            Some(branch) => &branch.spline,
+            None => self,
+        }
+    }
+
+    /// Convert an offset along a route to an offset along the main loop, so
+    /// that progress can be compared between routes.
+    #[must_use]
+    pub fn progress(&self, route: usize, offset: f32) -> f32 {
+        match self.route_branch(route) {
+            Some(branch) => {
+                let section = (branch.merge - branch.fork).rem_euclid(self.length);
+                let t = (offset / branch.spline.length).clamp(0.0, 1.0);
+                (branch.fork + section * t).rem_euclid(self.length)
+            }
+            None => offset,
+        }
+    }
+
+    /// Get the distance along the main loop from an offset to the fork of a
+    /// branch, which is negative if the fork is behind the offset.
+    fn distance_to_fork(&self, branch: &Branch, offset: f32) -> f32 {
+        let distance = (branch.fork - offset).rem_euclid(self.length);
+        if distance > self.length * 0.5 {
+            distance - self.length
+        } else {
+            distance
+        }
+    }
+
+    /// Get the branches that fork off of the main loop within a distance of an
+    /// offset, either ahead or behind.
+    pub fn forks_near(&self, offset: f32, distance: f32) -> impl Iterator<Item = usize> + '_ {
+        self.branches
+            .iter()
+            .enumerate()
+            .filter(move |(_, branch)| {
+                let to_fork = self.distance_to_fork(branch, offset);
+                to_fork > -distance && to_fork <= distance
+            })
+            .map(|(index, _)| index)
+    }
+
+    /// Get the length of a branch, and the length of the main loop section
+    /// that it replaces.
+    #[must_use]
+    pub fn branch_lengths(&self, branch: usize) -> (f32, f32) {
+        let branch = &self.branches[branch];
+        let section = (branch.merge - branch.fork).rem_euclid(self.length);
+        (branch.spline.length, section)
+    }
+
+    /// Get the point some distance ahead of an offset along a route. On the
+    /// main loop, the point follows the given branch if its fork is close.
+    #[must_use]
+    pub fn look_ahead(
+        &self,
+        route: usize,
+        offset: f32,
+        distance: f32,
+        branch: Option<usize>,
+    ) -> Vector {
+        match self.route_branch(route) {
+            Some(branch) => {
+                let target = offset + distance;
+                if target > branch.spline.length {
+                    // continue onto the main loop after the merge
+                    self.get_baked(branch.merge + target - branch.spline.length)
+                } else {
+                    branch.spline.get_baked(target)
+                }
+            }
+            None => {
+                if let Some(branch) = branch.and_then(|branch| self.branches.get(branch)) {
+                    let to_fork = self.distance_to_fork(branch, offset);
+                    if to_fork > -distance && to_fork <= distance {
+                        return branch.spline.get_baked(distance - to_fork);
+                    }
+                }
+                self.get_baked(offset + distance)
+            }
+        }
     }
 
     #[must_use]
-    #[allow(clippy::cast_possible_truncation)]
-    fn num_points(&self) -> u8 {
-        // the number of points is always known to be within u8 range
-        self.points.len() as u8
+    fn num_points(&self) -> f32 {
+        self.points.len() as f32
     }
 
     #[must_use]
@@ -186,7 +402,10 @@ impl Spline {
 
     #[must_use]
     pub fn interpolate(&self, offset: f32) -> Vector {
-        let offset = offset.rem_euclid(f32::from(self.num_points()));
+        if !self.closed {
+            return self.interpolate_path(offset);
+        }
+        let offset = offset.rem_euclid(self.num_points());
         let index = offset as usize;
         let offset = offset - offset.floor();
         let prev_index = (index + self.points.len() - 1) % self.points.len();
@@ -197,6 +416,33 @@ impl Spline {
         a * (1.0 - offset) + b * offset
     }
 
+    /// Interpolate a spline with two ends, where the first and last segments
+    /// only have one curve to follow.
+    fn interpolate_path(&self, offset: f32) -> Vector {
+        let last = self.points.len() - 1;
+        let offset = offset.clamp(0.0, last as f32);
+        let index = (offset as usize).min(last - 1);
+        let offset = offset - index as f32;
+        let a = (index > 0).then(|| {
+            let prev_mid = self.points[index - 1].control_mid;
+            self.bezier(index - 1, offset * (1.0 - prev_mid) + prev_mid)
+        });
+        let b = (index + 1 < last).then(|| {
+            let next_mid = self.points[index].control_mid;
+            self.bezier(index, offset * next_mid)
+        });
+        match (a, b) {
+            (Some(a), Some(b)) => a * (1.0 - offset) + b * offset,
+            (Some(a), None) => a,
+            (None, Some(b)) => b,
+            (None, None) => {
+                let start = self.points[index].point;
+                let end = self.points[index + 1].point;
+                start * (1.0 - offset) + end * offset
+            }
+        }
+    }
+
     pub fn add_baked(&mut self, position: f32) {
         let baked = Baked {
             point: self.interpolate(position),
@@ -212,23 +458,28 @@ impl Spline {
         });
     }
 
-    pub fn bake_recursive(&mut self, index: u8, begin: f32, end: f32, depth: usize) {
+    pub fn bake_recursive(&mut self, index: usize, begin: f32, end: f32, depth: usize) {
         if depth >= Self::MAX_BAKE_DEPTH {
             return;
         }
 
-        let v1 = self.interpolate(f32::from(index) + begin);
-        let v2 = self.interpolate(f32::from(index) + end);
+        let v1 = self.interpolate(index as f32 + begin);
+        let v2 = self.interpolate(index as f32 + end);
 
         if v1.dist_sq(v2) > BAKE_LENGTH_SQ {
             let mid = (begin + end) * 0.5;
             self.bake_recursive(index, begin, mid, depth + 1);
-            self.add_baked(f32::from(index) + mid);
+            self.add_baked(index as f32 + mid);
             self.bake_recursive(index, mid, end, depth + 1);
         }
     }
 
     fn convert_baked_offset(&self, baked_offset: f32) -> f32 {
+        let baked_offset = if self.closed {
+            baked_offset
+        } else {
+            baked_offset.clamp(0.0, self.length)
+        };
         // binary search
         let mut start = 0;
         let mut end = self.baked.len();
@@ -243,13 +494,17 @@ impl Spline {
         }
         // interpolate
         let next_index = (current + 1) % self.baked.len();
+        if next_index == 0 && !self.closed {
+            // at the end of a path
+            return self.baked[current].position;
+        }
         let offset_begin = self.baked[current].offset;
         let mut offset_end = self.baked[next_index].offset;
         let position_begin = self.baked[current].position;
         let mut position_end = self.baked[next_index].position;
         if next_index == 0 {
             offset_end += self.length;
-            position_end += f32::from(self.num_points());
+            position_end += self.num_points();
         }
         let interp = (baked_offset - offset_begin) / (offset_end - offset_begin);
         (1.0 - interp) * position_begin + interp * position_end
@@ -262,7 +517,7 @@ impl Spline {
 
     #[must_use]
     fn floor_div(&self, i: isize) -> (isize, &Point) {
-        let n = isize::from(self.num_points());
+        let n = self.points.len() as isize;
         let d = i / n;
         let d = if i < 0 && d * i != n { d - 1 } else { d };
         (d, &self.points[(i - d * n) as usize])
@@ -278,34 +533,75 @@ impl Spline {
         self.total_tilt * n as f32 + p.tilt
     }
 
-    fn lagrange(&self, i: isize, x: f32) -> f32 {
+    fn get_point_radius(&self, i: isize) -> f32 {
+        self.floor_div(i).1.radius
+    }
+
+    fn lagrange(&self, i: isize, x: f32, value: impl Fn(isize) -> f32) -> f32 {
         // TODO optimize
         let x0 = self.get_tilt_offset(i);
         let x1 = self.get_tilt_offset(i + 1);
         let x2 = self.get_tilt_offset(i + 2);
-        let y0 = self.get_tilt_radian(i);
-        let y1 = self.get_tilt_radian(i + 1);
-        let y2 = self.get_tilt_radian(i + 2);
+        let y0 = value(i);
+        let y1 = value(i + 1);
+        let y2 = value(i + 2);
         (y0 * (x - x1) / (x0 - x1) * (x - x2) / (x0 - x2))
             + (y1 * (x - x0) / (x1 - x0) * (x - x2) / (x1 - x2))
             + (y2 * (x - x0) / (x2 - x0) * (x - x1) / (x2 - x1))
     }
 
-    fn get_tilt(&self, offset: f32) -> f32 {
-        let pre_baked = offset.rem_euclid(self.length);
+    fn interpolate_value(&self, offset: f32, value: impl Fn(isize) -> f32) -> f32 {
+        let pre_baked = if self.closed {
+            offset.rem_euclid(self.length)
+        } else {
+            offset.clamp(0.0, self.length)
+        };
         let offset = self.convert_baked_offset(offset);
         let index = offset as isize;
-        let a = self.lagrange(index - 1, pre_baked);
-        let b = self.lagrange(index, pre_baked);
+        // paths can't use points past their ends
+        let clamp = |i: isize| {
+            if self.closed {
+                i
+            } else {
+                i.clamp(0, self.points.len() as isize - 3)
+            }
+        };
+        let a = self.lagrange(clamp(index - 1), pre_baked, &value);
+        let b = self.lagrange(clamp(index), pre_baked, &value);
         let offset = offset - offset.floor();
         a * (1.0 - offset) + b * offset
     }
 
+    fn get_tilt(&self, offset: f32) -> f32 {
+        self.interpolate_value(offset, |i| self.get_tilt_radian(i))
+    }
+
+    /// Get the radius of the spline at the given offset.
+    #[must_use]
+    pub fn get_radius(&self, offset: f32) -> f32 {
+        // interpolation can overshoot between points of very different widths
+        self.interpolate_value(offset, |i| self.get_point_radius(i))
+            .max(Course::MIN_WIDTH * 0.5)
+    }
+
+    /// Get the direction the spline heads in at the given offset.
+    #[must_use]
+    pub fn get_forward(&self, offset: f32) -> Vector {
+        let (sa, sb) = if self.closed {
+            (
+                (offset - FORWARD_VECTOR_SIZE).rem_euclid(self.length),
+                (offset + FORWARD_VECTOR_SIZE).rem_euclid(self.length),
+            )
+        } else {
+            let offset = offset.clamp(FORWARD_VECTOR_SIZE, self.length - FORWARD_VECTOR_SIZE);
+            (offset - FORWARD_VECTOR_SIZE, offset + FORWARD_VECTOR_SIZE)
+        };
+        (self.get_baked(sb) - self.get_baked(sa)).normalized()
+    }
+
     #[must_use]
     pub fn get_up_right(&self, offset: f32) -> (Vector, Vector) {
-        let sa = (offset - FORWARD_VECTOR_SIZE).rem_euclid(self.length);
-        let sb = (offset + FORWARD_VECTOR_SIZE).rem_euclid(self.length);
-        let target = (self.get_baked(sb) - self.get_baked(sa)).normalized();
+        let target = self.get_forward(offset);
         let look = Mtx::looking_at(target, Vector::Y_AXIS);
         let tilt = Mtx::axis_angle(&target, self.get_tilt(offset));
         let up = (Vector::Y_AXIS * look) * tilt;
@@ -313,19 +609,83 @@ impl Spline {
         (up, right)
     }
 
+    /// Check if an offset along the spline is between a start and end offset.
+    fn range_contains(&self, start: f32, end: f32, offset: f32) -> bool {
+        let start = start.rem_euclid(self.length);
+        let end = end.rem_euclid(self.length);
+        let offset = offset.rem_euclid(self.length);
+        if start <= end {
+            (start..=end).contains(&offset)
+        } else {
+            // the range crosses the finish line
+            offset >= start || offset <= end
+        }
+    }
+
+    /// Check if an offset along the spline has no floor.
+    #[must_use]
+    pub fn in_gap(&self, offset: f32) -> bool {
+        self.gaps
+            .iter()
+            .any(|gap| self.range_contains(gap.start, gap.end, offset))
+    }
+
+    /// Check if a baked segment lies entirely over a gap.
+    #[must_use]
+    pub fn is_segment_in_gap(&self, index: usize) -> bool {
+        let next_index = (index + 1) % self.baked.len();
+        self.gaps.iter().any(|gap| {
+            self.range_contains(gap.start, gap.end, self.baked[index].offset)
+                && self.range_contains(gap.start, gap.end, self.baked[next_index].offset)
+        })
+    }
+
+    /// Check if an offset along the spline is inside a tube.
+    #[must_use]
+    pub fn in_tube(&self, offset: f32) -> bool {
+        self.tubes
+            .iter()
+            .any(|tube| self.range_contains(tube.start, tube.end, offset))
+    }
+
+    /// Get the kind of surface at an offset along the spline and a horizontal
+    /// position relative to the radius.
+    #[must_use]
+    pub fn get_surface(&self, offset: f32, horizontal: f32) -> Option<SurfaceKind> {
+        self.surfaces
+            .iter()
+            .find(|surface| {
+                (surface.left..=surface.right).contains(&horizontal)
+                    && self.range_contains(surface.start, surface.end, offset)
+            })
+            .map(|surface| surface.kind)
+    }
+
     #[must_use]
     pub fn get_collision(&self, octree: &Octree, pos: Vector) -> Option<CollisionState> {
-        let offset = if let Some(offset) = octree.find_closest_offset(self, pos) {
-            offset
+        let (route, offset) = if let Some(closest) = octree.find_closest_offset(self, pos) {
+            closest
         } else {
             return None;
         };
-        let point = self.get_baked(offset);
-        let (up, right) = self.get_up_right(offset);
+        let spline = self.route(route);
+        let point = spline.get_baked(offset);
         let d = pos - point;
+        // the closest point may be at the edge of a gap or the end of the
+        // course that the position is past, while branches always end on the
+        // main route
+        let along = offset + spline.get_forward(offset).dot(&d);
+        let past_end = route == 0 && !self.closed && !(0.0..=self.length).contains(&along);
+        if past_end || spline.in_gap(along) {
+            return None;
+        }
+        let (up, right) = spline.get_up_right(offset);
+        let radius = spline.get_radius(offset);
+        if spline.in_tube(offset) {
+            return spline.get_tube_collision(d, offset, radius, route);
+        }
         let horizontal = right.dot(&d);
-        let radius = horizontal.abs();
-        if radius > Self::BOUNDS_RADIUS {
+        if horizontal.abs() > radius + Self::BOUNDS_MARGIN {
             // bounds radius check
             None
         } else {
@@ -337,7 +697,11 @@ impl Spline {
                     right,
                     height,
                     horizontal,
+                    radius,
+                    route,
                     offset,
+                    surface: spline.get_surface(offset, horizontal / radius),
+                    tube: false,
                 })
             } else {
                 None
@@ -345,6 +709,42 @@ impl Spline {
         }
     }
 
+    /// Get the collision state inside a tube, where the floor is the wall of
+    /// the tube and up points towards its center.
+    fn get_tube_collision(
+        &self,
+        d: Vector,
+        offset: f32,
+        radius: f32,
+        route: usize,
+    ) -> Option<CollisionState> {
+        let (up, right) = self.get_up_right(offset);
+        let forward = self.get_forward(offset);
+        // the position relative to the center of the tube, across the track
+        let v = d - up * radius;
+        let v = v - forward * forward.dot(&v);
+        let height = radius - v.mag();
+        // collision height check
+        if height < -Vehicle::COLLISION_DEPTH {
+            return None;
+        }
+        // the angle around the tube, starting from the bottom
+        let angle = right.dot(&v).atan2(-up.dot(&v));
+        let (sin, cos) = angle.sin_cos();
+        let horizontal = angle * radius;
+        Some(CollisionState {
+            up: up * cos - right * sin,
+            right: up * sin + right * cos,
+            height,
+            horizontal,
+            radius,
+            route,
+            offset,
+            surface: self.get_surface(offset, angle),
+            tube: true,
+        })
+    }
+
     #[must_use]
     pub fn get_offset_and_dist_sq(&self, point: Vector, index: usize) -> (f32, f32) {
         let next_index = (index + 1) % self.baked.len();
@@ -360,13 +760,52 @@ impl Spline {
     }
 
     fn prerender(&mut self) {
+        // sample every unit, and at the edges of gaps and tubes so that they
+        // start and end cleanly
+        let mut samples = vec![];
         let mut d = 0.0;
+        while d < self.length {
+            samples.push(d);
+            d += 1.0;
+        }
+        for gap in &self.gaps {
+            samples.push(gap.start.rem_euclid(self.length));
+            samples.push(gap.end.rem_euclid(self.length));
+        }
+        for tube in &self.tubes {
+            samples.push(tube.start.rem_euclid(self.length));
+            samples.push(tube.end.rem_euclid(self.length));
+        }
+        if !self.closed {
+            samples.push(self.length);
+        }
+        samples.sort_by(f32::total_cmp);
+        samples.dedup();
         let mut render_floor = vec![];
         let mut render_walls = vec![];
-        while d < self.length {
+        let mut open = vec![];
+        let mut tube = vec![];
+        for (index, &d) in samples.iter().enumerate() {
+            // check the middle of the section up to the next sample
+            let middle = match samples.get(index + 1) {
+                Some(&next) => Some((d + next) * 0.5),
+                // paths don't connect their ends
+                None if !self.closed => None,
+                None => Some((d + self.length) * 0.5),
+            };
+            match middle {
+                Some(middle) => {
+                    open.push(self.in_gap(middle));
+                    tube.push(self.in_tube(middle));
+                }
+                None => {
+                    open.push(true);
+                    tube.push(false);
+                }
+            }
             let p = self.get_baked(d);
             let (mut u, r) = self.get_up_right(d);
-            let r = r * Self::TRACK_RADIUS;
+            let r = r * self.get_radius(d);
             let mut pl = p - r;
             let mut pr = p + r;
             render_floor.push((pl, pr));
@@ -374,7 +813,6 @@ impl Spline {
             pl += u;
             pr += u;
             render_walls.push((pl, pr));
-            d += 1.0;
         }
         // in case no points loaded, don't draw
         if render_floor.is_empty() {
@@ -389,28 +827,121 @@ impl Spline {
             if other_index == render_floor.len() {
                 other_index = 0;
             }
+            let prev_index = if index == 0 {
+                render_floor.len() - 1
+            } else {
+                index - 1
+            };
             // get floor points
             let (l1, r1) = render_floor[index];
             let (l2, r2) = render_floor[other_index];
-            my_render_floor.push((l1, l2));
-            my_render_floor.push((r1, r2));
-            my_render_floor.push((l1, r1));
-            // get wall points
             let (wl1, wr1) = render_walls[index];
             let (wl2, wr2) = render_walls[other_index];
-            my_render_walls.push((l1, wl1));
-            my_render_walls.push((r1, wr1));
-            my_render_walls.push((wl1, wl2));
-            my_render_walls.push((wr1, wr2));
+            // leave sections over gaps open, and draw tubes as rings instead
+            let flat = !open[index] && !tube[index];
+            let prev_flat = !open[prev_index] && !tube[prev_index];
+            let solid_tube = !open[index] && tube[index];
+            let prev_solid_tube = !open[prev_index] && tube[prev_index];
+            if flat {
+                my_render_floor.push((l1, l2));
+                my_render_floor.push((r1, r2));
+                my_render_walls.push((wl1, wl2));
+                my_render_walls.push((wr1, wr2));
+            }
+            if flat || prev_flat {
+                my_render_floor.push((l1, r1));
+                my_render_walls.push((l1, wl1));
+                my_render_walls.push((r1, wr1));
+            }
+            if solid_tube || prev_solid_tube {
+                let ring = self.tube_ring(samples[index]);
+                for (side, &point) in ring.iter().enumerate() {
+                    my_render_floor.push((point, ring[(side + 1) % ring.len()]));
+                }
+                if solid_tube {
+                    let next_ring = self.tube_ring(samples[other_index]);
+                    for (&point, &next_point) in ring.iter().zip(&next_ring) {
+                        my_render_floor.push((point, next_point));
+                    }
+                }
+            }
         }
         self.render_floor = Arc::new(my_render_floor);
         self.render_walls = Arc::new(my_render_walls);
+        self.prerender_surfaces();
+    }
+
+    /// Get the points around the wall of a tube at an offset.
+    fn tube_ring(&self, offset: f32) -> Vec<Vector> {
+        let (up, right) = self.get_up_right(offset);
+        let radius = self.get_radius(offset);
+        let center = self.get_baked(offset) + up * radius;
+        (0..Self::TUBE_SIDES)
+            .map(|side| {
+                let angle = side as f32 * TAU / Self::TUBE_SIDES as f32;
+                let (sin, cos) = angle.sin_cos();
+                center + (right * sin - up * cos) * radius
+            })
+            .collect()
+    }
+
+    /// Get the point on the floor at an offset and a horizontal position
+    /// relative to the radius.
+    fn floor_point(&self, offset: f32, horizontal: f32) -> Vector {
+        let (_, right) = self.get_up_right(offset);
+        self.get_baked(offset) + right * (self.get_radius(offset) * horizontal)
+    }
+
+    fn prerender_surfaces(&mut self) {
+        let mut render_surfaces = vec![];
+        for surface in &self.surfaces {
+            let start = surface.start.rem_euclid(self.length);
+            let mut end = surface.end.rem_euclid(self.length);
+            if end < start {
+                end += self.length;
+            }
+            let (left, right) = (surface.left, surface.right);
+            let middle = (left + right) * 0.5;
+            let mut d = start;
+            while d < end {
+                let next = (d + 1.0).min(end);
+                let half = (d + next) * 0.5;
+                match surface.kind {
+                    SurfaceKind::Boost => {
+                        // chevrons pointing forward
+                        let tip = self.floor_point(next, middle);
+                        render_surfaces.push((self.floor_point(d, left), tip));
+                        render_surfaces.push((self.floor_point(d, right), tip));
+                    }
+                    SurfaceKind::Rough => {
+                        // crosshatching
+                        render_surfaces
+                            .push((self.floor_point(d, left), self.floor_point(next, right)));
+                        render_surfaces
+                            .push((self.floor_point(d, right), self.floor_point(next, left)));
+                    }
+                    SurfaceKind::Ice => {
+                        // dashed stripes along the track
+                        for h in [left, middle, right] {
+                            render_surfaces
+                                .push((self.floor_point(d, h), self.floor_point(half, h)));
+                        }
+                    }
+                }
+                d = next;
+            }
+        }
+        self.render_surfaces = Arc::new(render_surfaces);
     }
 
     pub fn render(&self, graph: &mut RenderGraph3d, walls: bool) {
         graph.lines(self.render_floor.clone());
+        graph.lines(self.render_surfaces.clone());
         if walls {
             graph.lines(self.render_walls.clone());
         }
+        for branch in &self.branches {
+            branch.spline.render(graph, walls);
+        }
     }
 }
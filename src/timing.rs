@@ -17,6 +17,18 @@
 pub const TICKS_PER_SECOND: u8 = 60;
 pub const TICK_DELTA: f32 = 1.0 / (TICKS_PER_SECOND as f32);
 
+/// Format a number of ticks as minutes, seconds, and milliseconds.
+#[must_use]
+pub fn format_ticks(ticks: u32) -> String {
+    let millis = u64::from(ticks) * 1000 / u64::from(TICKS_PER_SECOND);
+    format!(
+        "{}:{:02}.{:03}",
+        millis / 60000,
+        (millis / 1000) % 60,
+        millis % 1000
+    )
+}
+
 /// Wraps Instant for non-WASM targets.
 #[cfg(not(target_arch = "wasm32"))]
 struct InstantWrapper {
@@ -14,29 +14,148 @@
 //! You should have received a copy of the GNU General Public License
 //! along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, error::Error, sync::Arc};
 
-use crate::{assets::Asset, render::Mesh, vehicle::Model};
+use crate::{
+    assets::{Asset, AssetError, AssetErrorKind},
+    render::Mesh,
+    vehicle::Model,
+};
+
+/// The asset listing every vehicle.
+const MANIFEST: &str = "vehicles.cfg";
 
 /// The garage manages vehicle models.
 #[derive(Default)]
 pub struct Garage {
     by_name: HashMap<String, u16>,
     by_id: Vec<Model>,
+    /// The name of each model, by ID.
+    names: Vec<String>,
+    /// The mesh asset of each model loaded from the manifest, by name.
+    mesh_files: HashMap<String, String>,
 }
 
-impl Garage {
-    /// Loads the hardcoded vehicle models.
-    pub fn load_hardcoded(&mut self) {
-        let mesh = Mesh::load(&mut Asset::load("mesh_vehicle.bin").unwrap()).unwrap();
-        let model = Model {
+/// A vehicle as described in the manifest, before its mesh is loaded.
+struct Definition {
+    speed: f32,
+    acceleration: f32,
+    handling: f32,
+    anti_drift: f32,
+    mesh: String,
+}
+
+impl Default for Definition {
+    fn default() -> Self {
+        Self {
             speed: 15.0,
             acceleration: 7.0,
             handling: 1.5,
             anti_drift: 12.0,
-            mesh: Arc::new(mesh),
-        };
-        self.load("default".into(), model).unwrap();
+            mesh: String::from("mesh_vehicle.bin"),
+        }
+    }
+}
+
+impl Definition {
+    fn set(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        match key {
+            "speed" => self.speed = value.parse()?,
+            "acceleration" => self.acceleration = value.parse()?,
+            "handling" => self.handling = value.parse()?,
+            "anti_drift" => self.anti_drift = value.parse()?,
+            "mesh" => self.mesh = String::from(value),
+            _ => return Err("unknown property".into()),
+        }
+        Ok(())
+    }
+}
+
+/// Parse the manifest into named definitions, in the order they appear. Each
+/// vehicle starts with its name in brackets, followed by "key = value" lines.
+fn parse_manifest(text: &str) -> Vec<(String, Definition)> {
+    let mut definitions: Vec<(String, Definition)> = vec![];
+    for line in text.lines() {
+        let line = line.trim();
+        // skip blank lines and comments
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            definitions.push((String::from(name.trim()), Definition::default()));
+        } else if let Some((key, value)) = line.split_once('=') {
+            let key = key.trim();
+            match definitions.last_mut() {
+                Some((name, definition)) => {
+                    if let Err(e) = definition.set(key, value.trim()) {
+                        eprintln!("ignoring property {key} of vehicle {name}: {e}");
+                    }
+                }
+                None => eprintln!("ignoring property {key} outside of a vehicle"),
+            }
+        }
+    }
+    definitions
+}
+
+impl Garage {
+    /// Loads the vehicle models listed in the manifest. A custom manifest adds
+    /// to the default one, replacing any vehicles with the same name.
+    pub fn load_manifest(&mut self) -> Result<(), AssetError> {
+        let mut definitions = vec![];
+        for asset in Asset::load_all(MANIFEST)? {
+            definitions.extend(parse_manifest(&asset.into_string()?));
+        }
+        // vehicles may share meshes
+        let mut meshes: HashMap<String, Arc<Mesh>> = HashMap::new();
+        for (name, definition) in definitions {
+            let mesh = match meshes.get(&definition.mesh) {
+                Some(mesh) => mesh.clone(),
+                None => {
+                    let mesh = Arc::new(Mesh::load(&mut Asset::load(&definition.mesh)?)?);
+                    meshes.insert(definition.mesh.clone(), mesh.clone());
+                    mesh
+                }
+            };
+            let model = Model {
+                speed: definition.speed,
+                acceleration: definition.acceleration,
+                handling: definition.handling,
+                anti_drift: definition.anti_drift,
+                mesh,
+            };
+            match self.load(name.clone(), model) {
+                Ok(()) => {
+                    self.mesh_files.insert(name, definition.mesh);
+                }
+                Err(e) => eprintln!("failed to load vehicle: {e}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reload an asset that has changed, if any model uses it. Models keep
+    /// their IDs, so vehicles using them are unaffected.
+    #[cfg(not(any(target_os = "horizon", target_arch = "wasm32")))]
+    pub fn reload_asset(&mut self, name: &str) -> Result<(), AssetError> {
+        if name == MANIFEST {
+            return self.load_manifest();
+        }
+        let ids: Vec<u16> = self
+            .mesh_files
+            .iter()
+            .filter(|(_, file)| *file == name)
+            .filter_map(|(model, _)| self.get_id(model))
+            .collect();
+        if ids.is_empty() {
+            // not a mesh the garage uses
+            return Ok(());
+        }
+        let mesh = Arc::new(Mesh::load(&mut Asset::load(name)?)?);
+        for id in ids {
+            self.by_id[usize::from(id)].mesh = mesh.clone();
+        }
+        Ok(())
     }
 
     /// Try to load or replace a model with the given name.
@@ -51,6 +170,7 @@ impl Garage {
                 Err(_) => return Err("too many models in garage".into()),
             };
             self.by_id.push(value);
+            self.names.push(name.clone());
             self.by_name.insert(name, new_id);
         }
         Ok(())
@@ -61,8 +181,31 @@ impl Garage {
         Some(*self.by_name.get(name)?)
     }
 
+    /// Get a model ID by name, or an error if there is no such model.
+    pub fn find_id(&self, name: &str) -> Result<u16, AssetError> {
+        self.get_id(name).ok_or_else(|| {
+            AssetError::new(
+                MANIFEST,
+                0,
+                AssetErrorKind::MissingEntry(String::from(name)),
+            )
+        })
+    }
+
     /// Get a model from its ID.
     pub fn get_model(&self, id: u16) -> Option<&Model> {
         self.by_id.get(usize::from(id))
     }
+
+    /// Get a model's name from its ID.
+    pub fn get_name(&self, id: u16) -> Option<&str> {
+        self.names.get(usize::from(id)).map(String::as_str)
+    }
+
+    /// The number of models. IDs are always below this.
+    #[must_use]
+    pub fn num_models(&self) -> u16 {
+        // the number of models is kept within u16 range by load
+        self.by_id.len() as u16
+    }
 }
@@ -17,6 +17,7 @@
 use std::sync::Arc;
 
 use crate::{
+    course::SurfaceKind,
     linalg::{Length, Mtx, Quat, Vector},
     octree::Octree,
     platform::{Buttons, Controls},
@@ -46,7 +47,7 @@ pub struct Model {
 }
 
 pub struct ControllerGuidance<'a> {
-    /// The horizontal value.
+    /// The horizontal value, relative to the radius of the track.
     pub horizontal: f32,
     /// The vehicle position.
     pub position: Vector,
@@ -74,10 +75,23 @@ pub struct Vehicle {
     pub respawn_timer: Option<u8>,
     /// The location to respawn to.
     pub respawn_point: Vector,
+    /// When true, the controller's output is ignored and the vehicle brakes to
+    /// a stop, such as before the race starts.
+    pub locked: bool,
     /// The last seen spline horizontal.
     last_horizontal: f32,
-    /// The last seen spline offset.
+    /// The last seen spline route.
+    last_route: usize,
+    /// The last seen spline offset along the route.
     last_offset: f32,
+    /// The last seen spline offset along the main loop.
+    last_progress: f32,
+    /// The last seen spline up vector.
+    last_up: Vector,
+    /// The surface the vehicle is touching, if it's not plain track.
+    surface: Option<SurfaceKind>,
+    /// The multiplier for the top speed, changed by surface zones.
+    speed_factor: f32,
 }
 
 impl Vehicle {
@@ -97,6 +111,24 @@ impl Vehicle {
     /// How far ahead we look on the spline for guiding AI.
     const GUIDANCE_LOOKAHEAD: f32 = 4.0;
 
+    /// How quickly a locked vehicle slows down.
+    const STOP_DECELERATION: f32 = 20.0;
+
+    /// How far below the track the vehicle can fall over a gap before it's
+    /// respawned.
+    const GAP_FALL_DEPTH: f32 = 8.0;
+
+    /// The top speed multiplier after touching a boost pad.
+    const BOOST_SPEED_FACTOR: f32 = 1.5;
+    /// How quickly boost pads push the vehicle forward.
+    const BOOST_ACCELERATION: f32 = 40.0;
+    /// The top speed multiplier on rough surfaces.
+    const ROUGH_SPEED_FACTOR: f32 = 0.5;
+    /// How quickly the top speed returns to normal after a surface changes it.
+    const SPEED_FACTOR_APPROACH: f32 = 1.5;
+    /// The anti-drift multiplier on ice.
+    const ICE_ANTI_DRIFT_FACTOR: f32 = 0.2;
+
     pub fn new(pos: Vector, model_id: u16, controller: Box<dyn Controller>) -> Self {
         Self {
             position: pos,
@@ -107,8 +139,14 @@ impl Vehicle {
             steering: 0.0,
             respawn_timer: None,
             respawn_point: pos,
+            locked: false,
             last_horizontal: 0.0,
+            last_route: 0,
             last_offset: 0.0,
+            last_progress: 0.0,
+            last_up: Vector::Y_AXIS,
+            surface: None,
+            speed_factor: 1.0,
         }
     }
 
@@ -119,11 +157,35 @@ impl Vehicle {
             speed: self.signed_speed(),
             up: self.up_vector(),
             forward: self.forward_vector(),
-            target: spline.get_baked(self.last_offset + Self::GUIDANCE_LOOKAHEAD),
+            target: self.guidance_target(spline),
             controls,
         }
     }
 
+    /// Find the point somewhat ahead on the track that the vehicle aims for.
+    #[must_use]
+    pub fn guidance_target(&self, spline: &Spline) -> Vector {
+        spline.look_ahead(
+            self.last_route,
+            self.last_offset,
+            Self::GUIDANCE_LOOKAHEAD,
+            self.choose_branch(spline),
+        )
+    }
+
+    /// Ask the controller which branch to take, if any are coming up.
+    fn choose_branch(&self, spline: &Spline) -> Option<usize> {
+        if self.last_route != 0 {
+            return None;
+        }
+        spline
+            .forks_near(self.last_offset, Self::GUIDANCE_LOOKAHEAD)
+            .find(|&branch| {
+                let (branch_length, main_length) = spline.branch_lengths(branch);
+                self.controller.take_branch(branch_length, main_length)
+            })
+    }
+
     #[must_use]
     pub fn up_vector(&self) -> Vector {
         Mtx::from(self.rotation) * Vector::Y_AXIS
@@ -147,8 +209,8 @@ impl Vehicle {
     }
 
     fn handle_steering(&mut self, model: &Model) {
-        // only if we're not going to respawn
-        if self.respawn_timer.is_some() {
+        // only if we're not going to respawn or locked in place
+        if self.respawn_timer.is_some() || self.locked {
             return;
         }
         let steering = self.controller.steering();
@@ -171,11 +233,15 @@ impl Vehicle {
         if self.respawn_timer.is_some() {
             return;
         }
-        let pedal = match self.controller.pedal() {
-            Pedal::Accel => 1.0,
-            Pedal::Brake => -1.0,
-            Pedal::Neutral => 0.0,
-        };
+        if self.locked {
+            // brake until stopped
+            let speed = without.mag();
+            if speed > 0.0 {
+                *without -= *without * (speed.min(Self::STOP_DECELERATION * TICK_DELTA) / speed);
+            }
+            return;
+        }
+        let pedal = self.controller.pedal().amount();
         *without += forward * (pedal * model.acceleration * TICK_DELTA);
     }
 
@@ -191,7 +257,10 @@ impl Vehicle {
         let length = without.mag();
         *without = without.normalized();
 
-        let anti_drift = model.anti_drift;
+        let mut anti_drift = model.anti_drift;
+        if self.surface == Some(SurfaceKind::Ice) {
+            anti_drift *= Self::ICE_ANTI_DRIFT_FACTOR;
+        }
         let v = if f.dot(without) > b.dot(without) {
             without.approach(anti_drift, f)
         } else {
@@ -212,8 +281,21 @@ impl Vehicle {
         let mut gravity = self.gravity();
         let mut without = self.velocity - gravity;
         self.apply_acceleration_no_speed_cap(model, &mut without, forward);
+        // apply surface effects to the speed cap
+        if self.surface == Some(SurfaceKind::Boost) {
+            self.speed_factor = Self::BOOST_SPEED_FACTOR;
+            without += forward * (Self::BOOST_ACCELERATION * TICK_DELTA);
+        } else {
+            let target = if self.surface == Some(SurfaceKind::Rough) {
+                Self::ROUGH_SPEED_FACTOR
+            } else {
+                1.0
+            };
+            let t = (Self::SPEED_FACTOR_APPROACH * TICK_DELTA).min(1.0);
+            self.speed_factor += (target - self.speed_factor) * t;
+        }
         // speed cap
-        let speed = model.speed;
+        let speed = model.speed * self.speed_factor;
         if without.mag_sq() > speed * speed {
             without = without.normalized() * speed;
         }
@@ -230,19 +312,19 @@ impl Vehicle {
 
     fn collide_with_spline(&mut self, spline: &Spline, octree: &Octree, walls: bool) -> Vector {
         let mut new_gravity_vector = Vector::Y_AXIS;
+        self.surface = None;
         // only do this if the respawn timer is none
         if self.respawn_timer.is_none() {
             // check collision
             if let Some(state) = spline.get_collision(octree, self.position) {
                 let height = state.height;
                 let horizontal = state.horizontal;
-                if horizontal.abs() <= Spline::TRACK_RADIUS {
-                    if walls {
+                // tubes wrap all the way around, so there's no edge to fall off
+                if state.tube || horizontal.abs() <= state.radius {
+                    if walls && !state.tube {
                         // account for vehicle radius with wall collision
-                        let adjusted_radius = Spline::TRACK_RADIUS - Self::RADIUS;
-                        if horizontal.abs() > Spline::TRACK_RADIUS - Self::RADIUS
-                            && height <= Spline::WALL_HEIGHT
-                        {
+                        let adjusted_radius = state.radius - Self::RADIUS;
+                        if horizontal.abs() > adjusted_radius && height <= Spline::WALL_HEIGHT {
                             let right = state.right;
                             self.position -=
                                 right * (horizontal.abs() - adjusted_radius).copysign(horizontal);
@@ -253,9 +335,13 @@ impl Vehicle {
                     }
                     let up = state.up;
                     // update guidance info
+                    self.last_route = state.route;
                     self.last_offset = state.offset;
-                    self.last_horizontal = horizontal / -Spline::TRACK_RADIUS;
+                    self.last_progress = spline.progress(state.route, state.offset);
+                    self.last_up = up;
+                    self.last_horizontal = horizontal / -state.radius;
                     if height <= Self::GRAVITY_SNAP {
+                        self.surface = state.surface;
                         self.velocity = self.velocity_without_gravity();
                         // collided with floor, apply some friction
                         let with_friction = self.velocity
@@ -280,6 +366,9 @@ impl Vehicle {
                     // TODO is this necessary?
                     new_gravity_vector = new_gravity_vector.normalized();
                 }
+            } else if self.is_over_gap(spline) {
+                // keep falling the way we were until we land
+                new_gravity_vector = self.last_up;
             } else {
                 // we're out of bounds, we'll signal that we need a respawn
                 self.respawn_timer = Some(Self::RESPAWN_TIMER_INIT);
@@ -288,6 +377,15 @@ impl Vehicle {
         new_gravity_vector
     }
 
+    /// Check if the vehicle is above a gap after leaving the track, and hasn't
+    /// fallen too far to land.
+    fn is_over_gap(&self, spline: &Spline) -> bool {
+        let spline = spline.route(self.last_route);
+        let d = self.position - spline.get_baked(self.last_offset);
+        let along = spline.get_forward(self.last_offset).dot(&d);
+        spline.in_gap(self.last_offset + along) && self.last_up.dot(&d) > -Self::GAP_FALL_DEPTH
+    }
+
     fn update_collision(&mut self, spline: &Spline, octree: &Octree, walls: bool) {
         let new_gravity_vector = self.collide_with_spline(spline, octree, walls);
         let up = self.up_vector();
@@ -324,6 +422,30 @@ impl Vehicle {
         }
     }
 
+    /// Returns the pedal state requested by the controller.
+    #[must_use]
+    pub fn pedal(&self) -> Pedal {
+        self.controller.pedal()
+    }
+
+    /// Find where the vehicle is on a new spline, such as after the course is
+    /// reloaded, so that it doesn't follow a route that may no longer exist.
+    pub fn relocate(&mut self, spline: &Spline, octree: &Octree) {
+        let (route, offset) = octree
+            .find_closest_offset(spline, self.position)
+            .unwrap_or((0, 0.0));
+        self.last_route = route;
+        self.last_offset = offset;
+        self.last_progress = spline.progress(route, offset);
+    }
+
+    /// Returns the last seen offset along the main loop of the spline, which
+    /// branches are mapped onto.
+    #[must_use]
+    pub fn offset(&self) -> f32 {
+        self.last_progress
+    }
+
     #[must_use]
     pub fn signed_speed(&self) -> f32 {
         let v = self.velocity_without_gravity();
@@ -338,16 +460,33 @@ impl Vehicle {
     }
 }
 
-#[derive(Clone, Copy)]
-pub enum Pedal {
-    Accel,
-    Brake,
-    Neutral,
+#[derive(Clone, Copy, Default)]
+pub struct Pedal {
+    /// How far the accelerator is pressed, from 0 to 1.
+    pub throttle: f32,
+    /// How far the brake is pressed, from 0 to 1.
+    pub brake: f32,
 }
 
-impl Default for Pedal {
-    fn default() -> Self {
-        Self::Neutral
+impl Pedal {
+    pub const ACCEL: Self = Self {
+        throttle: 1.0,
+        brake: 0.0,
+    };
+
+    pub const BRAKE: Self = Self {
+        throttle: 0.0,
+        brake: 1.0,
+    };
+
+    /// The amount to accelerate by, from -1 to 1. Braking takes priority.
+    #[must_use]
+    pub fn amount(&self) -> f32 {
+        if self.brake > 0.0 {
+            -self.brake
+        } else {
+            self.throttle
+        }
     }
 }
 
@@ -359,6 +498,12 @@ pub trait Controller: Send {
     fn update(&mut self, _guidance: &ControllerGuidance) {
         // default implementation if no update logic needed
     }
+
+    /// Decide whether to take a branch, given its length and the length of
+    /// the section of the main loop it replaces.
+    fn take_branch(&self, _branch_length: f32, _main_length: f32) -> bool {
+        false
+    }
 }
 
 #[derive(Default)]
@@ -377,13 +522,19 @@ impl Controller for PlayerController {
     }
 
     fn update(&mut self, guidance: &ControllerGuidance) {
-        self.next_pedal = if guidance.controls.buttons.contains(Buttons::BACK) {
-            Pedal::Brake
-        } else if guidance.controls.buttons.contains(Buttons::OK) {
-            Pedal::Accel
+        let controls = &guidance.controls;
+        // buttons are fully pressed, triggers may be partially pressed
+        let throttle = if controls.buttons.contains(Buttons::OK) {
+            1.0
+        } else {
+            controls.throttle
+        };
+        let brake = if controls.buttons.contains(Buttons::BACK) {
+            1.0
         } else {
-            Pedal::Neutral
+            controls.brake
         };
+        self.next_pedal = Pedal { throttle, brake };
         self.next_steering = guidance.controls.steering;
     }
 }
@@ -392,7 +543,7 @@ pub struct EmptyController;
 
 impl Controller for EmptyController {
     fn pedal(&self) -> Pedal {
-        Pedal::Neutral
+        Pedal::default()
     }
 
     fn steering(&self) -> f32 {
@@ -412,6 +563,9 @@ impl AIController {
     const CAREFUL_ANGLE_RADS: f32 = 0.65;
     const MIN_SPEED: f32 = 7.0;
     const STEER_INTERP_STRENGTH: f32 = 10.0;
+    /// How much of the throttle to release in a turn that is almost tight
+    /// enough to brake for.
+    const TURN_EASE: f32 = 0.7;
 }
 
 impl Controller for AIController {
@@ -437,16 +591,53 @@ impl Controller for AIController {
             .approach_mut(Self::STEER_INTERP_STRENGTH, new_steering);
         if guidance.speed < Self::MIN_SPEED {
             // if moving rather slowly, accelerate so we don't go backwards
-            self.next_pedal = Pedal::Accel;
+            self.next_pedal = Pedal::ACCEL;
         } else if angle.abs() >= Self::CAREFUL_ANGLE_RADS {
             // if making a tight turn, brake
-            self.next_pedal = Pedal::Brake;
+            self.next_pedal = Pedal::BRAKE;
         } else if guidance.horizontal.abs() >= Self::BRAKE_RADIUS {
             // if far from center, brake
-            self.next_pedal = Pedal::Brake;
+            self.next_pedal = Pedal::BRAKE;
         } else {
-            // otherwise, accelerate
-            self.next_pedal = Pedal::Accel;
+            // otherwise, accelerate, easing off as the turn gets tighter
+            let tightness = angle.abs() / Self::CAREFUL_ANGLE_RADS;
+            self.next_pedal = Pedal {
+                throttle: 1.0 - tightness * Self::TURN_EASE,
+                brake: 0.0,
+            };
         }
     }
+
+    fn take_branch(&self, branch_length: f32, main_length: f32) -> bool {
+        // take the shortcut
+        branch_length < main_length
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The throttle the computer uses when its target is at an angle ahead.
+    fn ai_throttle(angle: f32) -> f32 {
+        let mut controller = AIController::default();
+        controller.update(&ControllerGuidance {
+            horizontal: 0.0,
+            position: Vector::ZERO,
+            speed: AIController::MIN_SPEED * 2.0,
+            up: Vector::Y_AXIS,
+            forward: Vector::Z_AXIS,
+            target: Vector::new(angle.sin(), 0.0, angle.cos()),
+            controls: &Controls::default(),
+        });
+        controller.pedal().throttle
+    }
+
+    #[test]
+    fn ai_eases_off_in_turns() {
+        assert_eq!(ai_throttle(0.0), 1.0);
+        let tight = ai_throttle(AIController::CAREFUL_ANGLE_RADS * 0.9);
+        assert!(tight < 0.5, "throttle was {tight} in a tight turn");
+        assert!(tight > 0.0);
+    }
 }
@@ -0,0 +1,120 @@
+//! Plays races through the headless simulation and checks how they turn out.
+
+use condux::headless::{Buttons, Controls, RaceSetup, Simulation, VehicleSummary};
+
+/// How many ticks a race may take before a test gives up on it.
+const TIME_LIMIT: u32 = 60 * 60 * 5;
+
+/// Holds accelerate and steers toward the vehicle's guidance target.
+fn full_throttle(vehicle: &VehicleSummary) -> Controls {
+    let angle = vehicle
+        .forward
+        .signed_angle(&(vehicle.target - vehicle.position), &vehicle.up);
+    Controls {
+        buttons: Buttons::OK,
+        steering: (-angle * 5.0).clamp(-1.0, 1.0),
+        ..Controls::default()
+    }
+}
+
+/// Run a number of ticks with the player driving at full throttle.
+fn drive(sim: &mut Simulation, ticks: u32) {
+    for _ in 0..ticks {
+        match sim.vehicles().first() {
+            Some(player) => sim.tick(full_throttle(player)),
+            None => return,
+        }
+    }
+}
+
+fn race(setup: RaceSetup) -> Simulation {
+    Simulation::race(setup).unwrap_or_else(|e| panic!("failed to load race: {e}"))
+}
+
+#[test]
+fn full_throttle_race_finishes() {
+    let mut sim = race(RaceSetup {
+        laps: 1,
+        opponents: 0,
+        ..RaceSetup::default()
+    });
+    drive(&mut sim, TIME_LIMIT);
+    // the results are shown once the only player finishes
+    assert!(
+        !sim.is_racing(),
+        "race did not finish within {TIME_LIMIT} ticks"
+    );
+}
+
+#[test]
+fn zero_laps_race_one_lap() {
+    let mut sim = race(RaceSetup {
+        laps: 0,
+        opponents: 0,
+        ..RaceSetup::default()
+    });
+    // the race would be over as soon as it started without a lap to race
+    drive(&mut sim, 60);
+    assert!(sim.is_racing());
+    drive(&mut sim, TIME_LIMIT);
+    assert!(!sim.is_racing());
+}
+
+#[test]
+fn vehicles_start_on_the_first_lap() {
+    let mut sim = race(RaceSetup::default());
+    // nobody can reach the finish line again in the first few seconds
+    for _ in 0..60 * 5 {
+        drive(&mut sim, 1);
+        for (i, vehicle) in sim.vehicles().iter().enumerate() {
+            assert_eq!(vehicle.laps, 0, "vehicle {i} wrapped around");
+        }
+    }
+}
+
+#[test]
+fn standings_follow_progress() {
+    let mut sim = race(RaceSetup::default());
+    for _ in 0..60 {
+        drive(&mut sim, 60);
+        if !sim.is_racing() {
+            break;
+        }
+        let vehicles = sim.vehicles();
+        let mut standings = sim.standings();
+        assert_eq!(standings.len(), vehicles.len());
+        // a finished vehicle is never behind one that is still racing
+        for pair in standings.windows(2) {
+            let (ahead, behind) = (&vehicles[pair[0]], &vehicles[pair[1]]);
+            match (ahead.finish_time, behind.finish_time) {
+                (Some(a), Some(b)) => assert!(a <= b),
+                (None, Some(_)) => panic!("vehicle {} finished behind a racer", pair[1]),
+                (Some(_), None) => {}
+                (None, None) => assert!(
+                    ahead.laps > behind.laps
+                        || (ahead.laps == behind.laps && ahead.offset >= behind.offset)
+                ),
+            }
+        }
+        // every vehicle appears exactly once
+        standings.sort_unstable();
+        assert!(standings.iter().copied().eq(0..vehicles.len()));
+    }
+}
+
+#[test]
+fn opponents_complete_laps() {
+    let mut sim = race(RaceSetup {
+        laps: 2,
+        opponents: 3,
+        ..RaceSetup::default()
+    });
+    // the player sits still, so only the opponents move
+    sim.run(60 * 90, |_| Controls::default());
+    assert!(sim.is_racing());
+    let vehicles = sim.vehicles();
+    for (i, vehicle) in vehicles.iter().enumerate().skip(1) {
+        assert!(vehicle.laps >= 1, "opponent {i} has not completed a lap");
+    }
+    assert!(vehicles[0].laps <= 0);
+}